@@ -6,6 +6,16 @@ pub mod scene;
 pub mod utils;
 
 pub fn goto_scene(owner: &Node, path: &str) {
+    enter_scene(owner, path);
+    request_autosave(owner, "SceneChange");
+    change_scene(owner, path);
+}
+
+///
+/// セーブデータに記録もオートセーブもせずにシーンを切り替える
+/// ロードした直後など、セーブデータのシーンへ戻るときに使う
+///
+pub fn change_scene(owner: &Node, path: &str) {
     let global = owner.get_node("/root/Global").unwrap();
     let global = unsafe { global.assume_safe() };
 
@@ -14,8 +24,41 @@ pub fn goto_scene(owner: &Node, path: &str) {
     }
 }
 
+///
+/// SceneTransitionがあれば画面を隠してから切り替える
+///
+pub fn transition_to_scene(owner: &Node, path: &str) {
+    match utils::find_node_in_group(owner, utils::SCENE_TRANSITION_GROUP) {
+        Some(transition) => unsafe {
+            transition.call("transition_to", &[path.to_variant()]);
+        },
+        None => change_scene(owner, path),
+    }
+}
+
+fn enter_scene(owner: &Node, path: &str) {
+    if let Some(save_data_manager) = owner.get_node("/root/SaveDataManager") {
+        unsafe {
+            save_data_manager
+                .assume_safe()
+                .call("enter_scene", &[path.to_variant()]);
+        }
+    }
+}
+
+pub fn request_autosave(owner: &Node, trigger: &str) {
+    if let Some(save_data_manager) = owner.get_node("/root/SaveDataManager") {
+        unsafe {
+            save_data_manager
+                .assume_safe()
+                .call("request_autosave", &[trigger.to_variant()]);
+        }
+    }
+}
+
 pub fn quit_game(global: Ref<Node, Shared>) {
     let global = unsafe { global.assume_safe() };
+    request_autosave(&global, "Quit");
 
     unsafe {
         global.call("quit_game", &[]);
@@ -24,9 +67,13 @@ pub fn quit_game(global: Ref<Node, Shared>) {
 
 fn init(handle: InitHandle) {
     handle.add_class::<native_lib::save_data::SaveDataManager>();
+    handle.add_class::<native_lib::event_scheduler::EventScheduler>();
+    handle.add_class::<native_lib::settings::SettingsManager>();
     handle.add_class::<crate::scene::title::TitleScene>();
     handle.add_class::<crate::scene::title::TitleEntries>();
     handle.add_class::<crate::utils::textbox::TextBox>();
+    handle.add_class::<crate::utils::confirm_dialog::ConfirmDialog>();
+    handle.add_class::<crate::utils::toast::Toast>();
     handle.add_class::<crate::utils::TextureDigit>();
     handle.add_class::<crate::scene::home::Calendar>();
     handle.add_class::<crate::scene::home::MagicBoardHome>();
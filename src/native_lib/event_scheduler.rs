@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use gdnative::prelude::*;
+
+use yuka_core::calendar::{
+    event::{self, CalendarEvent, EventScheduler as CoreEventScheduler},
+    GensoDate,
+};
+
+use crate::native_lib::{date_from_variant, date_to_dictionary, save_data::try_control_save_data};
+
+pub const EVENT_DATA_PATH: &str = "res://resources/data/events.toml";
+
+///
+/// events.tomlを読み込み、日次処理とEventSchedulerノードで共有する
+/// SaveDataManagerの_readyで一度だけ呼ぶ
+///
+pub fn load_events() -> Option<Arc<CoreEventScheduler>> {
+    let content = match crate::native_lib::read_text_file(EVENT_DATA_PATH) {
+        Some(content) => content,
+        None => {
+            godot_print!(
+                "{} not found, no calendar events are loaded",
+                EVENT_DATA_PATH
+            );
+            return None;
+        }
+    };
+
+    match CoreEventScheduler::from_toml_str(&content) {
+        Ok(scheduler) => {
+            let scheduler = Arc::new(scheduler);
+            event::set_event_scheduler(scheduler.clone());
+            Some(scheduler)
+        }
+        Err(e) => {
+            godot_print!("failed to parse {} -> {}", EVENT_DATA_PATH, e);
+            None
+        }
+    }
+}
+
+///
+/// イベントはSaveDataManagerが読み込んだものを使う
+///
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register_signals)]
+pub struct EventScheduler;
+
+#[methods]
+impl EventScheduler {
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "event_fired",
+            args: &[
+                SignalArgument {
+                    name: "id",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "name",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "date",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Dictionary),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+    }
+
+    fn new(_owner: &Node) -> Self {
+        EventScheduler
+    }
+
+    #[export]
+    fn _ready(&mut self, _owner: &Node) {
+        godot_print!("EventScheduler singleton loaded");
+    }
+
+    fn event_to_dictionary(event: &CalendarEvent, date: &GensoDate) -> Dictionary<Unique> {
+        let dict = Dictionary::new();
+        dict.insert("id", event.id.as_str());
+        dict.insert("name", event.name.as_str());
+        dict.insert("date", date_to_dictionary(date).into_shared());
+        dict
+    }
+
+    #[export]
+    fn get_events_on(&self, _owner: &Node, date: Variant) -> VariantArray {
+        let events = VariantArray::new();
+
+        if let Some(date) = date_from_variant(&date) {
+            for event in event::event_scheduler().events_on(&date) {
+                events.push(Self::event_to_dictionary(event, &date).into_shared());
+            }
+        }
+
+        events.into_shared()
+    }
+
+    ///
+    /// セーブデータが無い場合は空の配列を返す
+    ///
+    #[export]
+    fn get_today_events(&self, owner: &Node) -> VariantArray {
+        match try_control_save_data(|save_data| *save_data.get_date()) {
+            Ok(today) => {
+                self.get_events_on(owner, date_to_dictionary(&today).into_shared().to_variant())
+            }
+            Err(_) => VariantArray::new_shared(),
+        }
+    }
+
+    ///
+    /// 日次処理のEventsフェーズで集めたイベントを、SaveDataManagerが一つずつ通知する
+    ///
+    #[export]
+    fn fire_event(&self, owner: &Node, id: String, name: String, date: Variant) {
+        godot_print!("calendar event: {} ({})", name, id);
+        owner.emit_signal(
+            "event_fired",
+            &[Variant::from_str(id), Variant::from_str(name), date],
+        );
+    }
+}
@@ -1,274 +1,1297 @@
-use chrono::Datelike;
-use gdnative::prelude::*;
+pub mod codec;
 
-use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DisplayFromStr};
+use gdnative::{api::ProjectSettings, prelude::*};
 
 use std::{
-    cell::RefCell,
-    collections::HashMap,
-    fmt::Display,
-    io::{Read, Write},
-    str::FromStr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
 };
 
-use super::GensoDate;
+pub use yuka_core::{
+    calendar::{
+        week::{self, WeekAnchor},
+        TimeAction,
+    },
+    daily::{self, CalendarEventSystem, DailySystem, DailySystems},
+    flag::{FlagCondition, FlagValue, StoryFlags},
+    item::{
+        self, FertilizerItem, Item, ItemCatalog, ItemDefinition, ItemId, ItemManager, SoilItem,
+    },
+    save::{
+        autosave::{self, AutosavePolicy, AutosaveTrigger, Autosaver},
+        change::SaveDataChange,
+        compression::{self, Compression},
+        error,
+        format::{self, SaveFormat},
+        header, migration,
+        scene::{self, DialogueProgress, SceneRecord},
+        share,
+        slot::{self, SlotEntry, SlotRegistry},
+        statistics::{self, format_playtime},
+        storage,
+        worker::{SaveJob, SaveJobResult, SaveWorker},
+        NativeSaveData,
+    },
+};
+
+use crate::native_lib::{date_from_variant, date_to_dictionary, event_scheduler, settings};
+
+use error::SaveError;
+use header::SlotHeader;
+use storage::Recovered;
+
+pub const CALENDAR_DATA_PATH: &str = "res://resources/data/calendar.toml";
+pub const ITEM_DATA_PATH: &str = "res://resources/data/items.toml";
+
+/// セーブファイルを置くディレクトリ
+pub const SAVE_DIR: &str = "user://saves";
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum SoilItem {
-    Fuyodo,
-    Kurotsuchi,
-    Baiyodo,
+/// セーブディレクトリ導入前に、カレントディレクトリに作られていたスロット
+pub const LEGACY_SAVE_SLOTS: [&str; 6] =
+    ["Entry1", "Entry2", "Entry3", "Entry4", "Entry5", "Entry6"];
+
+/// オートセーブのエントリに表示する名前
+pub const AUTOSAVE_DISPLAY_NAME: &str = "オートセーブ";
+
+/// バックグラウンドのセーブ/ロードと共有するので、スレッド間で使えるようにしておく
+static CURRENT_SAVEDATA: Mutex<Option<NativeSaveData>> = Mutex::new(None);
+
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register_signals)]
+pub struct SaveDataManager {
+    daily_systems: DailySystems,
+    encode_options: codec::EncodeOptions,
+    autosaver: Autosaver,
+    /// 鍵の素を渡さずにビルドした場合はNone。セーブとロードはNoSaveKeyで失敗する
+    worker: Option<SaveWorker>,
 }
 
-impl SoilItem {
-    pub fn get_display_name(&self) -> &str {
-        match self {
-            Self::Fuyodo => "腐葉土",
-            Self::Kurotsuchi => "黒土",
-            Self::Baiyodo => "培養土",
+#[methods]
+impl SaveDataManager {
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        for name in ["save_failed", "load_failed"] {
+            builder.add_signal(Signal {
+                name,
+                args: &[
+                    SignalArgument {
+                        name: "slot",
+                        default: Variant::from_str("None"),
+                        export_info: ExportInfo::new(VariantType::GodotString),
+                        usage: PropertyUsage::DEFAULT,
+                    },
+                    SignalArgument {
+                        name: "reason",
+                        default: Variant::from_str("None"),
+                        export_info: ExportInfo::new(VariantType::GodotString),
+                        usage: PropertyUsage::DEFAULT,
+                    },
+                ],
+            });
+        }
+
+        for name in ["save_started", "save_completed", "load_completed"] {
+            builder.add_signal(Signal {
+                name,
+                args: &[SignalArgument {
+                    name: "slot",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                }],
+            });
+        }
+
+        builder.add_signal(Signal {
+            name: "load_recovered",
+            args: &[
+                SignalArgument {
+                    name: "slot",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "generation",
+                    default: Variant::from_i64(0),
+                    export_info: ExportInfo::new(VariantType::I64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+
+        for name in ["items_changed", "flags_changed", "slots_changed"] {
+            builder.add_signal(Signal { name, args: &[] });
         }
+
+        builder.add_signal(Signal {
+            name: "items_discarded",
+            args: &[
+                SignalArgument {
+                    name: "id",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "count",
+                    default: Variant::from_i64(0),
+                    export_info: ExportInfo::new(VariantType::I64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+
+        builder.add_signal(Signal {
+            name: "date_changed",
+            args: &[SignalArgument {
+                name: "date",
+                default: Variant::new(),
+                export_info: ExportInfo::new(VariantType::Dictionary),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+
+        builder.add_signal(Signal {
+            name: "time_changed",
+            args: &[SignalArgument {
+                name: "time",
+                default: Variant::from_str("None"),
+                export_info: ExportInfo::new(VariantType::GodotString),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+
+        builder.add_signal(Signal {
+            name: "day_advanced",
+            args: &[
+                SignalArgument {
+                    name: "old",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Dictionary),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "new",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Dictionary),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
     }
-}
 
-impl Display for SoilItem {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::Fuyodo => write!(f, "腐葉土"),
-            Self::Kurotsuchi => write!(f, "黒土"),
-            Self::Baiyodo => write!(f, "培養土"),
+    fn new(_owner: &Node) -> Self {
+        SaveDataManager {
+            daily_systems: DailySystems::new(),
+            encode_options: codec::EncodeOptions::default(),
+            autosaver: Autosaver::default(),
+            worker: match codec::save_keys() {
+                Ok(keys) => Some(SaveWorker::spawn(
+                    keys,
+                    codec::legacy_decrypt,
+                    storage::BACKUP_GENERATIONS,
+                )),
+                Err(e) => {
+                    godot_print!("{}", e);
+                    None
+                }
+            },
         }
     }
-}
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum FertilizerItem {
-    Aburakasu,
-    Gyohi,
-    ShimoGoe,
-    Chemical,
-}
+    ///
+    /// 日付が変わったときに実行する処理を登録する
+    /// イベントの発火はEventSchedulerノードが行うので、ここには登録しない
+    ///
+    pub fn register_daily_system(&mut self, system: Box<dyn DailySystem>) {
+        self.daily_systems.register(system);
+    }
+
+    #[export]
+    fn _ready(&mut self, _owner: &Node) {
+        godot_print!("SaveDataManager singleton loaded");
+        Self::load_calendar_data();
+        Self::load_item_data();
+        self.load_event_data();
+        Self::import_legacy_slots();
+
+        // SettingsManagerが先に読み込まれていた場合も、ここで設定のポリシーと形式にする
+        let (policy, format) =
+            settings::control_settings(|settings| (settings.autosave_policy, settings.save_format));
+        self.autosaver.set_policy(policy);
+        self.encode_options.format = format;
+        self.autosaver
+            .resume(autosave::AUTOSAVE_SLOTS.iter().map(|slot| {
+                Self::load_slot_summary(slot)
+                    .ok()
+                    .map(|header| header.saved_at)
+            }));
+    }
+
+    fn load_calendar_data() {
+        let content = match crate::native_lib::read_text_file(CALENDAR_DATA_PATH) {
+            Some(content) => content,
+            None => {
+                godot_print!("{} not found, use default week anchor", CALENDAR_DATA_PATH);
+                return;
+            }
+        };
 
-impl FertilizerItem {
-    pub fn get_display_name(&self) -> &str {
-        match self {
-            Self::Aburakasu => "油粕",
-            Self::Gyohi => "魚肥",
-            Self::ShimoGoe => "下肥",
-            Self::Chemical => "化学肥料",
+        match WeekAnchor::from_toml_str(&content) {
+            Ok(anchor) => week::set_week_anchor(anchor),
+            Err(e) => godot_print!("failed to parse {} -> {}", CALENDAR_DATA_PATH, e),
         }
     }
-}
 
-impl Display for FertilizerItem {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::Aburakasu => write!(f, "油粕"),
-            Self::Gyohi => write!(f, "魚肥"),
-            Self::ShimoGoe => write!(f, "下肥"),
-            Self::Chemical => write!(f, "化学肥料"),
+    ///
+    /// カレンダーイベントはEventsフェーズの日次処理として集め、日次処理の後にEventSchedulerから通知する
+    ///
+    fn load_event_data(&mut self) {
+        if let Some(scheduler) = event_scheduler::load_events() {
+            self.register_daily_system(Box::new(CalendarEventSystem::new(scheduler)));
         }
     }
-}
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum Item {
-    Soil(SoilItem),
-    Fertilizer(FertilizerItem),
-}
+    fn load_item_data() {
+        let content = match crate::native_lib::read_text_file(ITEM_DATA_PATH) {
+            Some(content) => content,
+            None => {
+                godot_print!("{} not found, use builtin item catalog", ITEM_DATA_PATH);
+                return;
+            }
+        };
 
-impl Item {
-    pub fn get_display_name(&self) -> &str {
-        match self {
-            Self::Soil(soil_item) => soil_item.get_display_name(),
-            Self::Fertilizer(fertilizer) => fertilizer.get_display_name(),
+        match ItemCatalog::from_toml_str(&content) {
+            Ok(catalog) => item::set_item_catalog(catalog),
+            Err(e) => godot_print!("failed to parse {} -> {}", ITEM_DATA_PATH, e),
         }
     }
-}
 
-impl Display for Item {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::Soil(soil) => soil.fmt(f),
-            Self::Fertilizer(fert) => fert.fmt(f),
+    pub fn slot_registry() -> SlotRegistry {
+        let dir = ProjectSettings::godot_singleton().globalize_path(SAVE_DIR);
+        SlotRegistry::new(dir.to_string())
+    }
+
+    pub fn slot_path(slot: &str) -> Result<PathBuf, SaveError> {
+        Self::slot_registry().checked_path(slot)
+    }
+
+    fn import_legacy_slots() {
+        let registry = Self::slot_registry();
+
+        for slot in LEGACY_SAVE_SLOTS.iter() {
+            match registry.import(slot, Path::new(slot)) {
+                Ok(true) => godot_print!("{} was moved to {}", slot, registry.path(slot).display()),
+                Ok(false) => (),
+                Err(e) => godot_print!("failed to import {} -> {}", slot, e),
+            }
         }
     }
-}
 
-impl FromStr for Item {
-    type Err = String;
+    ///
+    /// 登録済みのスロットに手動でセーブする
+    ///
+    #[export]
+    fn save(&mut self, owner: &Node, file_name: Variant) {
+        let file_name = file_name.to_string();
+
+        if autosave::is_reserved_slot(&file_name) {
+            Self::emit_save_failed(
+                owner,
+                &file_name,
+                &SaveError::ReservedSlot(file_name.clone()),
+            );
+            return;
+        }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "腐葉土" => Ok(Item::Soil(SoilItem::Fuyodo)),
-            "黒土" => Ok(Item::Soil(SoilItem::Kurotsuchi)),
-            "培養土" => Ok(Item::Soil(SoilItem::Baiyodo)),
-            "油粕" => Ok(Item::Fertilizer(FertilizerItem::Aburakasu)),
-            "魚肥" => Ok(Item::Fertilizer(FertilizerItem::Gyohi)),
-            "下肥" => Ok(Item::Fertilizer(FertilizerItem::ShimoGoe)),
-            "化学肥料" => Ok(Item::Fertilizer(FertilizerItem::Chemical)),
-            _ => Err("BUG".to_string()),
+        match Self::slot_registry().get(&file_name) {
+            Ok(Some(_)) => self.write_slot(owner, &file_name),
+            Ok(None) => Self::emit_save_failed(
+                owner,
+                &file_name,
+                &SaveError::UnknownSlot(file_name.clone()),
+            ),
+            Err(e) => Self::emit_save_failed(owner, &file_name, &e),
         }
     }
-}
 
-#[serde_as]
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ItemManager {
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    items: HashMap<Item, usize>,
-}
+    ///
+    /// 新しいスロットを作ってセーブし、そのidを返す。失敗した場合は空文字列
+    ///
+    #[export]
+    fn save_to_new_slot(&mut self, owner: &Node) -> String {
+        match Self::slot_registry().create(None) {
+            Ok(entry) => {
+                owner.emit_signal("slots_changed", &[]);
+                self.write_slot(owner, &entry.id);
+                entry.id
+            }
+            Err(e) => {
+                Self::emit_save_failed(owner, "", &e);
+                String::new()
+            }
+        }
+    }
+
+    #[export]
+    fn create_slot(&mut self, owner: &Node, name: String) -> String {
+        let name = Some(name.as_str()).filter(|name| !name.is_empty());
 
-impl ItemManager {
-    pub fn new() -> Self {
-        ItemManager {
-            items: HashMap::new(),
+        match Self::slot_registry().create(name) {
+            Ok(entry) => {
+                owner.emit_signal("slots_changed", &[]);
+                entry.id
+            }
+            Err(e) => {
+                godot_print!("failed to create slot -> {}", e);
+                String::new()
+            }
         }
     }
 
-    pub fn add_items(&mut self, item: Item, count: usize) {
-        if let Some(bag_count) = self.items.get_mut(&item) {
-            *bag_count += count;
+    #[export]
+    fn delete_slot(&mut self, owner: &Node, slot: String) -> bool {
+        Self::update_slots(owner, "delete", &slot, |registry| registry.delete(&slot))
+    }
+
+    #[export]
+    fn duplicate_slot(&mut self, owner: &Node, slot: String) -> String {
+        let mut id = String::new();
+        Self::update_slots(owner, "duplicate", &slot, |registry| {
+            registry.duplicate(&slot).map(|entry| id = entry.id)
+        });
+        id
+    }
+
+    #[export]
+    fn rename_slot(&mut self, owner: &Node, slot: String, name: String) -> bool {
+        Self::update_slots(owner, "rename", &slot, |registry| {
+            registry.rename(&slot, &name)
+        })
+    }
+
+    fn update_slots<F>(owner: &Node, action: &str, slot: &str, f: F) -> bool
+    where
+        F: FnOnce(&SlotRegistry) -> Result<(), SaveError>,
+    {
+        match f(&Self::slot_registry()) {
+            Ok(()) => {
+                owner.emit_signal("slots_changed", &[]);
+                true
+            }
+            Err(e) => {
+                godot_print!("failed to {} {} -> {}", action, slot, e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// 現在のセーブデータのスナップショットを取り、暗号化と書き込みはワーカースレッドで行う
+    /// 結果は_processでsave_completedかsave_failedとして通知する
+    ///
+    fn write_slot(&mut self, owner: &Node, file_name: &str) {
+        match Self::snapshot() {
+            Ok(data) => self.submit_save(owner, file_name, data),
+            Err(e) => Self::emit_save_failed(owner, file_name, &e),
+        }
+    }
+
+    fn submit_save(&mut self, owner: &Node, file_name: &str, data: NativeSaveData) {
+        let worker = match self.worker.as_mut() {
+            Some(worker) => worker,
+            None => return Self::emit_save_failed(owner, file_name, &SaveError::NoSaveKey),
+        };
+        let path = match Self::slot_path(file_name) {
+            Ok(path) => path,
+            Err(e) => return Self::emit_save_failed(owner, file_name, &e),
+        };
+
+        owner.emit_signal("save_started", &[Variant::from_str(file_name)]);
+        worker.submit(SaveJob::Save {
+            slot: file_name.to_string(),
+            path,
+            data: Box::new(data),
+            options: self.encode_options,
+        });
+    }
+
+    ///
+    /// 現在のセーブデータを共有用のテキストコードにする。失敗した場合は空文字列
+    ///
+    #[export]
+    fn export_save_code(&self, _owner: &Node, compress: bool) -> String {
+        let compression = if compress {
+            Compression::Deflate
         } else {
-            self.items.insert(item, count);
+            Compression::None
+        };
+
+        // 共有するだけなのでセーブしたことにはしない
+        let code = try_control_save_data(|save_data| save_data.clone())
+            .and_then(|mut data| codec::export_share_code(&mut data, compression));
+
+        match code {
+            Ok(code) => code,
+            Err(e) => {
+                godot_print!("failed to export save code -> {}", e);
+                String::new()
+            }
         }
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<Item, usize> {
-        self.items.iter()
+    ///
+    /// 共有コードをスロットに書き込む。slotが空文字列の場合は新しいスロットを作る
+    /// 読めないコードの場合は、理由をsave_failedで通知する
+    ///
+    #[export]
+    fn import_save_code(&mut self, owner: &Node, slot: String, code: String) -> bool {
+        if autosave::is_reserved_slot(&slot) {
+            Self::emit_save_failed(owner, &slot, &SaveError::ReservedSlot(slot.clone()));
+            return false;
+        }
+
+        let data = match codec::import_share_code(&code) {
+            Ok(data) => data,
+            Err(e) => {
+                Self::emit_save_failed(owner, &slot, &e);
+                return false;
+            }
+        };
+
+        let registry = Self::slot_registry();
+        let target = if slot.is_empty() {
+            registry.create(None).map(|entry| {
+                owner.emit_signal("slots_changed", &[]);
+                entry.id
+            })
+        } else {
+            match registry.get(&slot) {
+                Ok(Some(_)) => Ok(slot.clone()),
+                Ok(None) => Err(SaveError::UnknownSlot(slot.clone())),
+                Err(e) => Err(e),
+            }
+        };
+
+        match target {
+            Ok(target) => {
+                self.submit_save(owner, &target, data);
+                true
+            }
+            Err(e) => {
+                Self::emit_save_failed(owner, &slot, &e);
+                false
+            }
+        }
     }
 
-    pub fn size(&self) -> usize {
-        self.items.len()
+    fn emit_save_failed(owner: &Node, file_name: &str, e: &SaveError) {
+        godot_print!("failed to save {} -> {}", file_name, e);
+        owner.emit_signal(
+            "save_failed",
+            &[
+                Variant::from_str(file_name),
+                Variant::from_str(e.to_string()),
+            ],
+        );
     }
-}
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct NativeSaveData {
-    real_date: String,
-    items: ItemManager,
-    date: GensoDate,
-}
+    ///
+    /// セーブするために現在のセーブデータを複製し、複製にだけ保存日時を記録する
+    /// 現在のセーブデータには、書き込みが成功したときに記録する
+    ///
+    pub fn snapshot() -> Result<NativeSaveData, SaveError> {
+        let mut data = try_control_save_data(|save_data| save_data.clone())?;
+        data.mark_saved();
+        Ok(data)
+    }
+
+    #[export]
+    fn _process(&mut self, owner: &Node, delta: f64) {
+        // プレイ時間は変更として通知しない
+        let _ = try_control_save_data_mut(|save_data| save_data.add_playtime(delta));
 
-impl NativeSaveData {
-    pub fn new() -> Self {
-        NativeSaveData {
-            items: ItemManager::new(),
-            date: GensoDate::new(112, 5, 1),
-            real_date: "None".to_string(),
+        for result in self.worker.iter_mut().flat_map(SaveWorker::poll) {
+            Self::handle_job_result(owner, result);
         }
+
+        Self::emit_changes(owner);
     }
 
-    pub fn get_items(&self) -> &ItemManager {
-        &self.items
+    ///
+    /// control_save_data_mutなどで記録された変更を、まとめてシグナルで通知する
+    ///
+    fn emit_changes(owner: &Node) {
+        let (changes, date, time) = match try_control_save_data_mut(|save_data| {
+            (
+                save_data.take_changes(),
+                *save_data.get_date(),
+                save_data.get_time().to_string(),
+            )
+        }) {
+            Ok(changes) => changes,
+            Err(_) => return,
+        };
+
+        for change in changes {
+            match change {
+                SaveDataChange::Items => owner.emit_signal("items_changed", &[]),
+                SaveDataChange::Date => owner.emit_signal(
+                    "date_changed",
+                    &[date_to_dictionary(&date).into_shared().to_variant()],
+                ),
+                SaveDataChange::Time => {
+                    owner.emit_signal("time_changed", &[Variant::from_str(&time)])
+                }
+                SaveDataChange::Flags => owner.emit_signal("flags_changed", &[]),
+            };
+        }
     }
 
-    pub fn add_items(&mut self, item: Item, count: usize) {
-        self.items.add_items(item, count);
+    #[export]
+    fn is_busy(&self, _owner: &Node) -> bool {
+        self.worker.as_ref().map_or(false, SaveWorker::is_busy)
     }
 
-    pub fn get_date(&self) -> &GensoDate {
-        &self.date
+    ///
+    /// 最後にセーブしてから進行状況が変わっているか
+    ///
+    #[export]
+    fn is_dirty(&self, _owner: &Node) -> bool {
+        try_control_save_data(|save_data| save_data.is_dirty()).unwrap_or(false)
     }
 
-    pub fn get_real_date(&self) -> &str {
-        self.real_date.as_str()
+    fn handle_job_result(owner: &Node, result: SaveJobResult) {
+        match result {
+            SaveJobResult::Saved {
+                slot,
+                revision,
+                result: Ok(()),
+            } => {
+                // 書き込めたときだけ、現在のセーブデータにも保存日時を記録する
+                let _ = try_control_save_data_mut(|save_data| {
+                    save_data.mark_clean(revision);
+                    save_data.mark_saved();
+                });
+                owner.emit_signal("save_completed", &[Variant::from_str(slot)]);
+            }
+            SaveJobResult::Saved {
+                slot,
+                result: Err(e),
+                ..
+            } => {
+                Self::emit_save_failed(owner, &slot, &e);
+            }
+            SaveJobResult::Loaded {
+                slot,
+                result: Ok(loaded),
+            } => {
+                if loaded.is_recovered_from_backup() {
+                    godot_print!(
+                        "{} was recovered from backup generation {}",
+                        slot,
+                        loaded.generation
+                    );
+                    owner.emit_signal(
+                        "load_recovered",
+                        &[
+                            Variant::from_str(&slot),
+                            Variant::from_u64(loaded.generation as u64),
+                        ],
+                    );
+                }
+
+                if loaded.data.format == codec::SaveFileFormat::Legacy {
+                    // 認証導入前のフォーマット。次回のセーブで新しいフォーマットに置き換わる
+                    godot_print!("legacy save format detected");
+                }
+
+                let mut save_data = loaded.data.data;
+                // ゲームのデータからアイテムを消した場合など。数は残しておく
+                item::with_item_catalog(|catalog| {
+                    for id in save_data.get_items().unknown_ids(catalog) {
+                        godot_print!("{} has unknown item {}", slot, id);
+                    }
+                });
+                save_data.begin_session();
+                let scene = save_data.get_scene().path.clone();
+                set_current_save_data(save_data);
+                owner.emit_signal("load_completed", &[Variant::from_str(slot)]);
+
+                // セーブしたときのシーンを作り直し、各ウィジェットを新しいセーブデータで初期化させる
+                crate::transition_to_scene(owner, &scene);
+            }
+            SaveJobResult::Loaded {
+                slot,
+                result: Err(e),
+            } => {
+                godot_print!("failed to load {} -> {}", slot, e);
+                owner.emit_signal(
+                    "load_failed",
+                    &[Variant::from_str(slot), Variant::from_str(e.to_string())],
+                );
+            }
+        }
     }
-}
 
-thread_local!(static CURRENT_SAVEDATA: RefCell<Option<NativeSaveData>> = {
-    RefCell::new(None)
-});
+    ///
+    /// 設定されたポリシーで許されていればオートセーブする
+    /// セーブデータが無い場合 (タイトル画面など) は何もしない
+    ///
+    pub fn autosave(&mut self, owner: &Node, trigger: AutosaveTrigger) {
+        if try_control_save_data(|_| ()).is_err() {
+            return;
+        }
 
-#[derive(NativeClass)]
-#[inherit(Node)]
-pub struct SaveDataManager;
+        if let Some(slot) = self.autosaver.begin(trigger, Instant::now()) {
+            godot_print!("autosave ({:?}) -> {}", trigger, slot);
+            self.write_slot(owner, slot);
+        }
 
-#[methods]
-impl SaveDataManager {
-    fn new(_owner: &Node) -> Self {
-        SaveDataManager
+        // 終了時は書き込みが終わるまで待つ
+        if trigger == AutosaveTrigger::Quit {
+            for result in self.worker.iter_mut().flat_map(SaveWorker::wait_all) {
+                Self::handle_job_result(owner, result);
+            }
+        }
     }
 
     #[export]
-    fn _ready(&mut self, _owner: &Node) {
-        godot_print!("SaveDataManager singleton loaded");
+    fn request_autosave(&mut self, owner: &Node, trigger: String) {
+        match trigger.parse::<AutosaveTrigger>() {
+            Ok(trigger) => self.autosave(owner, trigger),
+            Err(e) => godot_print!("{}", e),
+        }
     }
 
     #[export]
-    fn save(&mut self, _owner: &Node, file_name: Variant) {
-        control_save_data_mut(|save_data| {
-            let date =  chrono::Local::today();
-            save_data.real_date = format!("{}-{}-{}", date.year(), date.month(), date.day());
+    fn set_autosave_policy(&mut self, _owner: &Node, policy: String) -> bool {
+        match policy.parse::<AutosavePolicy>() {
+            Ok(policy) => {
+                self.autosaver.set_policy(policy);
+                true
+            }
+            Err(e) => {
+                godot_print!("{}", e);
+                false
+            }
+        }
+    }
 
-            let mut file = std::fs::File::create(file_name.to_string()).unwrap();
+    #[export]
+    fn get_autosave_policy(&self, _owner: &Node) -> String {
+        self.autosaver.policy().to_string()
+    }
 
-            file.write_all(
-                crate::native_lib::crypt::crypt_str(&toml::to_string(save_data).unwrap())
-                    .unwrap()
-                    .as_slice(),
-            )
-            .unwrap();
-            file.flush().unwrap();
-        });
+    ///
+    /// これ以降のセーブで使う形式。形式はスロットのヘッダに記録されるので、読み込みには影響しない
+    /// 設定のsave_formatを変えるとSettingsManagerから呼ばれる。ここで変えただけでは設定ファイルに残らない
+    ///
+    #[export]
+    fn set_save_format(&mut self, _owner: &Node, format: String) -> bool {
+        match format.parse::<SaveFormat>() {
+            Ok(format) => {
+                self.encode_options.format = format;
+                true
+            }
+            Err(e) => {
+                godot_print!("{}", e);
+                false
+            }
+        }
     }
 
     #[export]
-    fn load(&mut self, _owner: &Node, file_name: GodotString) {
-        let loaded_save_data = Self::load_native_save_data(file_name);
+    fn get_save_format(&self, _owner: &Node) -> String {
+        self.encode_options.format.to_string()
+    }
 
-        if loaded_save_data.is_none() {
-            return;
+    ///
+    /// 一番新しいオートセーブのスロットとその概要
+    ///
+    pub fn latest_autosave() -> Option<(String, SlotHeader)> {
+        autosave::AUTOSAVE_SLOTS
+            .iter()
+            .filter_map(|slot| {
+                Self::load_slot_summary(slot)
+                    .ok()
+                    .map(|header| (slot.to_string(), header))
+            })
+            .max_by_key(|(_, header)| header.saved_at)
+    }
+
+    ///
+    /// セーブ画面のエントリ名を実際のスロット名にする
+    /// オートセーブのエントリは一番新しいオートセーブを指す
+    /// スロットのidとして使えないエントリ名はエラーにする
+    ///
+    pub fn entry_slot(entry: &str) -> Result<String, SaveError> {
+        if entry == autosave::AUTOSAVE_ENTRY {
+            Ok(Self::latest_autosave()
+                .map_or(autosave::AUTOSAVE_SLOTS[0].to_string(), |(slot, _)| slot))
+        } else if slot::is_valid_slot_id(entry) {
+            Ok(entry.to_string())
+        } else {
+            Err(SaveError::InvalidSlot(entry.to_string()))
         }
+    }
 
-        CURRENT_SAVEDATA.with(|current_save_data| {
-            current_save_data.replace_with(|_| Some(loaded_save_data.unwrap()));
-        });
+    ///
+    /// 一番最近セーブしたスロット。オートセーブも含める。無い場合は空文字列
+    ///
+    #[export]
+    fn get_latest_slot(&self, _owner: &Node) -> String {
+        Self::load_all_slot_summaries()
+            .into_iter()
+            .filter_map(|(entry, summary)| summary.ok().map(|header| (entry.id, header)))
+            .chain(Self::latest_autosave())
+            .max_by_key(|(_, header)| header.saved_at)
+            .map_or(String::new(), |(slot, _)| slot)
+    }
+
+    ///
+    /// ワーカースレッドで読み込み、_processで現在のセーブデータを置き換える
+    /// 置き換えた後は、セーブしたときのシーンへ移る
+    ///
+    #[export]
+    fn load(&mut self, owner: &Node, file_name: GodotString) {
+        let file_name = file_name.to_string();
+        let (slot, path) = match Self::entry_slot(&file_name)
+            .and_then(|slot| Self::slot_path(&slot).map(|path| (slot, path)))
+        {
+            Ok(slot) => slot,
+            Err(e) => {
+                return Self::handle_job_result(
+                    owner,
+                    SaveJobResult::Loaded {
+                        slot: file_name,
+                        result: Err(e),
+                    },
+                )
+            }
+        };
+        godot_print!("load! -> {}", slot);
+
+        match self.worker.as_mut() {
+            Some(worker) => worker.submit(SaveJob::Load { slot, path }),
+            None => Self::handle_job_result(
+                owner,
+                SaveJobResult::Loaded {
+                    slot,
+                    result: Err(SaveError::NoSaveKey),
+                },
+            ),
+        }
     }
 
-    pub fn load_native_save_data(file_name: GodotString) -> Option<NativeSaveData> {
+    pub fn load_native_save_data(
+        file_name: GodotString,
+    ) -> Result<Recovered<NativeSaveData>, SaveError> {
         godot_print!("load! -> {}", file_name.to_string());
 
-        match std::fs::File::open(file_name.to_string().as_str()) {
-            Ok(mut file) => {
-                let mut buf = Vec::new();
-                match file.read_to_end(&mut buf) {
-                    Ok(_) => (),
-                    Err(_) => return None,
-                }
+        storage::read_with_recovery(
+            &Self::slot_path(&file_name.to_string())?,
+            storage::BACKUP_GENERATIONS,
+            Self::decode_native_save_data,
+        )
+    }
+
+    pub fn decode_native_save_data(buf: &[u8]) -> Result<NativeSaveData, SaveError> {
+        let decoded = codec::decode(buf)?;
+
+        if decoded.format == codec::SaveFileFormat::Legacy {
+            // 認証導入前のフォーマット。次回のセーブで新しいフォーマットに置き換わる
+            godot_print!("legacy save format detected");
+        }
+
+        Ok(decoded.data)
+    }
+
+    ///
+    /// ヘッダだけを読んでスロットの概要を返す
+    /// ヘッダを持たない古いセーブデータは、全体を読み込んで作る
+    ///
+    pub fn load_slot_summary(file_name: &str) -> Result<SlotHeader, SaveError> {
+        match header::read_slot_header(&Self::slot_path(file_name)?) {
+            Ok(Some(header)) => Ok(header),
+            _ => Self::load_native_save_data(GodotString::from_str(file_name))
+                .map(|loaded| SlotHeader::from_save_data(&loaded.data)),
+        }
+    }
+
+    pub fn load_all_slot_summaries() -> Vec<(SlotEntry, Result<SlotHeader, SaveError>)> {
+        match Self::slot_registry().list() {
+            Ok(slots) => slots
+                .into_iter()
+                .map(|entry| {
+                    let summary = Self::load_slot_summary(&entry.id);
+                    (entry, summary)
+                })
+                .collect(),
+            Err(e) => {
+                godot_print!("failed to read slot list -> {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    ///
+    /// スロットの表示名。オートセーブと未登録のスロットはidから決める
+    ///
+    pub fn slot_display_name(slot: &str) -> String {
+        if autosave::is_reserved_slot(slot) {
+            return AUTOSAVE_DISPLAY_NAME.to_string();
+        }
+
+        match Self::slot_registry().get(slot) {
+            Ok(Some(entry)) => entry.name,
+            _ => slot.to_string(),
+        }
+    }
+
+    pub fn slot_summary_to_dictionary(
+        slot: &str,
+        name: &str,
+        summary: &Result<SlotHeader, SaveError>,
+    ) -> Dictionary<Unique> {
+        let dict = Dictionary::new();
+        dict.insert("slot", slot);
+        dict.insert("name", name);
+
+        match summary {
+            Ok(header) => {
+                dict.insert("empty", false);
+                dict.insert("schema_version", header.schema_version);
+                dict.insert("date", header.date.to_short_string());
+                dict.insert("real_date", header.real_date.as_str());
+                dict.insert("saved_at", header.saved_at);
+                dict.insert("playtime_secs", header.playtime_secs);
+                dict.insert("playtime", format_playtime(header.playtime_secs));
+                dict.insert("days_played", header.days_played);
+                dict.insert("item_total", header.item_total as u64);
+            }
+            Err(_) => dict.insert("empty", true),
+        }
+
+        dict
+    }
+
+    #[export]
+    fn get_slot_summaries(&self, _owner: &Node) -> VariantArray {
+        let summaries = VariantArray::new();
+
+        for (entry, summary) in Self::load_all_slot_summaries() {
+            summaries.push(
+                Self::slot_summary_to_dictionary(&entry.id, &entry.name, &summary).into_shared(),
+            );
+        }
+
+        summaries.into_shared()
+    }
+
+    ///
+    /// 進んだ日数を返す。セーブデータが無い場合や、日付が範囲を超える場合は何もせず0を返す
+    ///
+    #[export]
+    fn advance_time(&mut self, owner: &Node, minutes: u32) -> u32 {
+        match try_control_save_data_mut(|save_data| save_data.advance_time(minutes)) {
+            Ok(Ok(days)) => {
+                self.process_new_days(owner);
+                days
+            }
+            Ok(Err(e)) => {
+                godot_print!("failed to advance time -> {}", e);
+                0
+            }
+            Err(e) => {
+                godot_print!("failed to advance time -> {}", e);
+                0
+            }
+        }
+    }
+
+    #[export]
+    fn advance_time_by_action(&mut self, owner: &Node, action: String) -> u32 {
+        let action = match action.parse::<TimeAction>() {
+            Ok(action) => action,
+            Err(e) => {
+                godot_print!("{}", e);
+                return 0;
+            }
+        };
 
-                let content = crate::native_lib::crypt::decrypt_str(&buf);
+        match try_control_save_data_mut(|save_data| save_data.advance_time_by_action(action)) {
+            Ok(Ok(days)) => {
+                self.process_new_days(owner);
+                days
+            }
+            Ok(Err(e)) => {
+                godot_print!("failed to advance time -> {}", e);
+                0
+            }
+            Err(e) => {
+                godot_print!("failed to advance time -> {}", e);
+                0
+            }
+        }
+    }
 
-                let loaded_save_data = toml::from_str(&content.unwrap());
+    ///
+    /// セーブデータが無い場合は空文字列
+    ///
+    #[export]
+    fn get_time_string(&self, _owner: &Node) -> String {
+        try_control_save_data(|save_data| save_data.get_time().to_string()).unwrap_or_default()
+    }
 
-                match loaded_save_data {
-                    Ok(loaded_save_data) => Some(loaded_save_data),
-                    Err(_) => None,
+    ///
+    /// セーブデータが無い場合は空文字列
+    ///
+    #[export]
+    fn get_day_phase(&self, _owner: &Node) -> String {
+        try_control_save_data(|save_data| format!("{:?}", save_data.get_time().day_phase()))
+            .unwrap_or_default()
+    }
+
+    ///
+    /// 眠って次の日の朝にする
+    /// 起きる前に時間を進めて日付が変わっていて、その日の処理が済んでいる場合は何も通知しない
+    ///
+    #[export]
+    fn advance_day(&mut self, owner: &Node) {
+        match try_control_save_data_mut(|save_data| save_data.sleep_until_morning()) {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                godot_print!("failed to advance day -> {}", e);
+                return;
+            }
+            Err(e) => {
+                godot_print!("failed to advance day -> {}", e);
+                return;
+            }
+        }
+
+        self.process_new_days(owner);
+    }
+
+    ///
+    /// まだ日次処理をしていない日があれば処理する
+    /// 日次処理、カレンダーイベントの通知、day_advancedの発行、オートセーブの順に行う
+    ///
+    fn process_new_days(&mut self, owner: &Node) {
+        let daily_systems = &mut self.daily_systems;
+        let advance = match try_control_save_data_mut(|save_data| {
+            daily::process_new_days(save_data, daily_systems)
+        }) {
+            Ok(Some(advance)) => advance,
+            Ok(None) => return,
+            Err(e) => {
+                godot_print!("failed to process new days -> {}", e);
+                return;
+            }
+        };
+
+        godot_print!("day advanced: {} -> {}", advance.old, advance.new);
+
+        if let Some(event_scheduler) = owner.get_node("/root/EventScheduler") {
+            for (date, event) in &advance.events {
+                unsafe {
+                    event_scheduler.assume_safe().call(
+                        "fire_event",
+                        &[
+                            Variant::from_str(&event.id),
+                            Variant::from_str(&event.name),
+                            date_to_dictionary(date).into_shared().to_variant(),
+                        ],
+                    );
                 }
             }
-            Err(_) => None,
         }
+
+        owner.emit_signal(
+            "day_advanced",
+            &[
+                date_to_dictionary(&advance.old).into_shared().to_variant(),
+                date_to_dictionary(&advance.new).into_shared().to_variant(),
+            ],
+        );
+
+        self.autosave(owner, AutosaveTrigger::DayChange);
+    }
+
+    #[export]
+    fn set_flag(&mut self, _owner: &Node, key: String, value: Variant) -> bool {
+        let value = match flag_value_from_variant(&value) {
+            Some(value) => value,
+            None => {
+                godot_print!("unsupported flag value for {}: {:?}", key, value.get_type());
+                return false;
+            }
+        };
+
+        match try_control_save_data_mut(|save_data| save_data.set_flag(&key, value)) {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                godot_print!("{}", e);
+                false
+            }
+            Err(e) => {
+                godot_print!("failed to set flag {} -> {}", key, e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// 設定されていなければnullを返す
+    ///
+    #[export]
+    fn get_flag(&self, _owner: &Node, key: String) -> Variant {
+        try_control_save_data(|save_data| {
+            save_data.get_flags().get(&key).map(flag_value_to_variant)
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    #[export]
+    fn has_flag(&self, _owner: &Node, key: String) -> bool {
+        try_control_save_data(|save_data| save_data.get_flags().contains(&key)).unwrap_or(false)
+    }
+
+    #[export]
+    fn remove_flag(&mut self, _owner: &Node, key: String) -> bool {
+        try_control_save_data_mut(|save_data| save_data.remove_flag(&key).is_some())
+            .unwrap_or(false)
+    }
+
+    #[export]
+    fn get_flags_in(&self, _owner: &Node, namespace: String) -> Dictionary {
+        let dict = Dictionary::new();
+
+        let _ = try_control_save_data(|save_data| {
+            for (key, value) in save_data.get_flags().in_namespace(&namespace) {
+                dict.insert(key, flag_value_to_variant(value));
+            }
+        });
+
+        dict.into_shared()
+    }
+
+    ///
+    /// ダイアログの条件と同じ書式 (例: "npc.met_marisa", "shop.visits >= 3") を評価する
+    ///
+    #[export]
+    fn check_condition(&self, _owner: &Node, condition: String) -> bool {
+        match condition.parse::<FlagCondition>() {
+            Ok(condition) => {
+                let flags = try_control_save_data(|save_data| save_data.get_flags().clone())
+                    .unwrap_or_else(|_| StoryFlags::new());
+                condition.evaluate(&flags)
+            }
+            Err(e) => {
+                godot_print!("{}", e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// 日時はUNIX時間。記録が無ければnull
+    ///
+    #[export]
+    fn get_statistics(&self, _owner: &Node) -> Dictionary {
+        let dict = Dictionary::new();
+
+        let _ = try_control_save_data(|save_data| {
+            let stats = save_data.get_statistics();
+            let timestamp = |t: Option<i64>| t.map_or(Variant::new(), Variant::from_i64);
+
+            dict.insert("playtime_secs", stats.playtime_secs);
+            dict.insert("playtime", format_playtime(stats.playtime_secs));
+            dict.insert(
+                "created_at",
+                timestamp(stats.created_at.map(|t| t.timestamp())),
+            );
+            dict.insert(
+                "last_saved_at",
+                timestamp(stats.last_saved_at.map(|t| t.timestamp())),
+            );
+            dict.insert("session_count", stats.session_count);
+            dict.insert("days_played", stats.days_played);
+            dict.insert("items_acquired", stats.items_acquired);
+            dict.insert("flowers_grown", stats.flowers_grown);
+        });
+
+        dict.into_shared()
+    }
+
+    ///
+    /// 持っている数がstack_limitを超える分は捨て、捨てた数をitems_discardedで通知する
+    /// 実際に足した数を返す
+    ///
+    #[export]
+    fn add_items(&mut self, owner: &Node, id: String, count: i64) -> i64 {
+        if count <= 0 {
+            return 0;
+        }
+
+        let added = match try_control_save_data_mut(|save_data| {
+            save_data.add_items(ItemId::new(&id), count as usize)
+        }) {
+            Ok(added) => added as i64,
+            Err(e) => {
+                godot_print!("failed to add items -> {}", e);
+                return 0;
+            }
+        };
+
+        if added < count {
+            owner.emit_signal(
+                "items_discarded",
+                &[Variant::from_str(&id), Variant::from_i64(count - added)],
+            );
+        }
+
+        added
+    }
+
+    #[export]
+    fn get_item_count(&self, _owner: &Node, id: String) -> i64 {
+        let id = item::with_item_catalog(|catalog| catalog.resolve(&ItemId::new(&id)));
+        try_control_save_data(|save_data| save_data.get_items().count(&id) as i64).unwrap_or(0)
+    }
+
+    ///
+    /// カタログに無いidの場合はnullを返す
+    ///
+    #[export]
+    fn get_item_info(&self, _owner: &Node, id: String) -> Variant {
+        let language = settings::control_settings(|settings| settings.language.clone());
+
+        item::with_item_catalog(|catalog| {
+            catalog.get(&ItemId::new(&id)).map(|item| {
+                item_info_to_dictionary(item, &language)
+                    .into_shared()
+                    .to_variant()
+            })
+        })
+        .unwrap_or_default()
+    }
+
+    #[export]
+    fn get_date(&self, _owner: &Node) -> Variant {
+        match try_control_save_data(|save_data| *save_data.get_date()) {
+            Ok(date) => date_to_dictionary(&date).into_shared().to_variant(),
+            Err(_) => Variant::new(),
+        }
+    }
+
+    ///
+    /// goto_sceneから呼ばれ、移った先のシーンを記録する
+    ///
+    #[export]
+    fn enter_scene(&mut self, _owner: &Node, path: String) {
+        let _ = try_control_save_data_mut(|save_data| save_data.enter_scene(&path));
     }
 
     #[export]
     fn create_new_entry_and_set_as_current(&mut self, _owner: &Node) {
         godot_print!("create new save entry");
-        CURRENT_SAVEDATA.with(|current_save_data| {
-            current_save_data.replace_with(|_| Some(NativeSaveData::new()))
-        });
+        let mut save_data = NativeSaveData::new();
+        save_data.begin_session();
+        set_current_save_data(save_data);
     }
 }
 
+///
+/// bool, int, String, 日付のDictionaryをフラグの値にする
+///
+pub fn flag_value_from_variant(value: &Variant) -> Option<FlagValue> {
+    match value.get_type() {
+        VariantType::Bool => value.try_to_bool().map(FlagValue::Bool),
+        VariantType::I64 => value.try_to_i64().map(FlagValue::Int),
+        VariantType::GodotString => value
+            .try_to_godot_string()
+            .map(|s| FlagValue::Str(s.to_string())),
+        VariantType::Dictionary => date_from_variant(value).map(FlagValue::Date),
+        _ => None,
+    }
+}
+
+pub fn item_info_to_dictionary(item: &ItemDefinition, language: &str) -> Dictionary<Unique> {
+    let dict = Dictionary::new();
+    dict.insert("id", item.id.as_str());
+    dict.insert("category", item.category.as_str());
+    dict.insert("name", item.display_name(language));
+    dict.insert("description", item.description(language));
+    dict.insert("icon", item.icon.as_deref().unwrap_or_default());
+    dict.insert("price", item.price);
+    dict.insert("stack_limit", item.stack_limit as i64);
+
+    let effect = Dictionary::new();
+    for (key, value) in &item.effect {
+        effect.insert(key.as_str(), *value);
+    }
+    dict.insert("effect", effect.into_shared());
+
+    dict
+}
+
+pub fn flag_value_to_variant(value: &FlagValue) -> Variant {
+    match value {
+        FlagValue::Bool(b) => Variant::from_bool(*b),
+        FlagValue::Int(n) => Variant::from_i64(*n),
+        FlagValue::Str(s) => Variant::from_str(s),
+        FlagValue::Date(date) => date_to_dictionary(date).into_shared().to_variant(),
+    }
+}
+
+///
+/// 現在のセーブデータを置き換える。次のフレームですべての変更が通知される
+///
+pub fn set_current_save_data(mut save_data: NativeSaveData) {
+    save_data.mark_all_changed();
+    *CURRENT_SAVEDATA.lock().unwrap() = Some(save_data);
+}
+
 pub fn control_save_data<F, R>(f: F) -> R
 where
     F: FnOnce(&NativeSaveData) -> R,
 {
-    CURRENT_SAVEDATA.with(|current_save_data| f(current_save_data.borrow().as_ref().unwrap()))
+    f(CURRENT_SAVEDATA.lock().unwrap().as_ref().unwrap())
 }
 
 pub fn control_save_data_mut<F, R>(f: F) -> R
 where
     F: FnOnce(&mut NativeSaveData) -> R,
 {
-    CURRENT_SAVEDATA.with(|current_save_data| f(current_save_data.borrow_mut().as_mut().unwrap()))
+    f(CURRENT_SAVEDATA.lock().unwrap().as_mut().unwrap())
+}
+
+pub fn try_control_save_data<F, R>(f: F) -> Result<R, SaveError>
+where
+    F: FnOnce(&NativeSaveData) -> R,
+{
+    CURRENT_SAVEDATA
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(f)
+        .ok_or(SaveError::NoCurrentSave)
+}
+
+pub fn try_control_save_data_mut<F, R>(f: F) -> Result<R, SaveError>
+where
+    F: FnOnce(&mut NativeSaveData) -> R,
+{
+    CURRENT_SAVEDATA
+        .lock()
+        .unwrap()
+        .as_mut()
+        .map(f)
+        .ok_or(SaveError::NoCurrentSave)
 }
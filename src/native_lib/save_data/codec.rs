@@ -0,0 +1,48 @@
+pub use yuka_core::save::codec::{Decoded, EncodeOptions, SaveFileFormat};
+
+use yuka_core::{
+    crypt::SaveKeys,
+    save::{compression::Compression, error::SaveError, share, NativeSaveData},
+};
+
+///
+/// セーブファイルの鍵。鍵の素は公開しているソースには含めず、ビルド時に環境変数YUKA_SAVE_SECRETで渡す
+/// 渡さずにビルドした場合、セーブ、ロード、共有コードはすべてSaveError::NoSaveKeyで失敗する
+///
+pub fn save_keys() -> Result<SaveKeys, SaveError> {
+    SaveKeys::from_build_env().ok_or(SaveError::NoSaveKey)
+}
+
+pub fn legacy_decrypt(buf: &[u8]) -> Result<String, String> {
+    crate::native_lib::crypt::decrypt_str(buf).map_err(|e| e.to_string())
+}
+
+pub fn encode(save_data: &mut NativeSaveData) -> Result<Vec<u8>, SaveError> {
+    yuka_core::save::codec::encode(&save_keys()?, save_data)
+}
+
+pub fn encode_with(
+    save_data: &mut NativeSaveData,
+    options: EncodeOptions,
+) -> Result<Vec<u8>, SaveError> {
+    yuka_core::save::codec::encode_with(&save_keys()?, save_data, options)
+}
+
+pub fn decrypt(buf: &[u8]) -> Result<(toml::Value, SaveFileFormat), SaveError> {
+    yuka_core::save::codec::decrypt(&save_keys()?, buf, legacy_decrypt)
+}
+
+pub fn decode(buf: &[u8]) -> Result<Decoded, SaveError> {
+    yuka_core::save::codec::decode(&save_keys()?, buf, legacy_decrypt)
+}
+
+pub fn export_share_code(
+    save_data: &mut NativeSaveData,
+    compression: Compression,
+) -> Result<String, SaveError> {
+    share::export(&save_keys()?, save_data, compression)
+}
+
+pub fn import_share_code(code: &str) -> Result<NativeSaveData, SaveError> {
+    share::import(&save_keys()?, code)
+}
@@ -0,0 +1,335 @@
+use gdnative::{
+    api::{AudioServer, InputEventKey, InputMap, ProjectSettings, TranslationServer, OS},
+    prelude::*,
+};
+use std::{path::PathBuf, sync::Mutex};
+
+pub use yuka_core::settings::{GameSettings, SettingsError, Volumes, WindowMode, SETTINGS_VERSION};
+
+use yuka_core::save::storage;
+
+/// セーブデータとは別の、プレイヤーごとの設定ファイル
+pub const SETTINGS_PATH: &str = "user://settings.toml";
+
+/// エンジン側に反映する設定。起動時とリセット時にすべて反映する
+const ENGINE_SETTINGS: [&str; 6] = [
+    "window_mode",
+    "volume",
+    "language",
+    "key_bindings",
+    "autosave_policy",
+    "save_format",
+];
+
+/// 設定の音量を反映するオーディオバス
+const VOLUME_BUSES: [(&str, fn(&Volumes) -> f64); 4] = [
+    ("Master", |volumes| volumes.master),
+    ("BGM", |volumes| volumes.bgm),
+    ("SE", |volumes| volumes.se),
+    ("Voice", |volumes| volumes.voice),
+];
+
+/// 無音のときのdB
+const SILENT_DB: f64 = -80.0;
+
+/// 読み込むのは最初に参照されたとき。SettingsManagerより先に他のノードが参照してもよい
+static CURRENT_SETTINGS: Mutex<Option<GameSettings>> = Mutex::new(None);
+
+///
+/// ゲーム全体の設定を持つシングルトン
+/// 変更はすぐにファイルへ書き込み、setting_changedで通知する
+///
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register_signals)]
+pub struct SettingsManager;
+
+#[methods]
+impl SettingsManager {
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "setting_changed",
+            args: &[
+                SignalArgument {
+                    name: "key",
+                    default: Variant::from_str("None"),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "value",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Nil),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+    }
+
+    fn new(_owner: &Node) -> Self {
+        SettingsManager
+    }
+
+    #[export]
+    fn _ready(&self, owner: &Node) {
+        godot_print!("SettingsManager singleton loaded");
+
+        for key in ENGINE_SETTINGS {
+            Self::apply(owner, key);
+        }
+    }
+
+    fn settings_path() -> PathBuf {
+        let path = ProjectSettings::godot_singleton().globalize_path(SETTINGS_PATH);
+        PathBuf::from(path.to_string())
+    }
+
+    fn load_settings() -> GameSettings {
+        let path = Self::settings_path();
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => {
+                godot_print!("{} not found, use default settings", SETTINGS_PATH);
+                return GameSettings::default();
+            }
+        };
+
+        match GameSettings::from_toml_str(&content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                godot_print!(
+                    "failed to read {} -> {}, use default settings",
+                    SETTINGS_PATH,
+                    e
+                );
+                GameSettings::default()
+            }
+        }
+    }
+
+    fn save_settings(settings: &GameSettings) {
+        let result = settings
+            .to_toml_string()
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                storage::write_atomic(&Self::settings_path(), content.as_bytes(), 1)
+                    .map_err(|e| e.to_string())
+            });
+
+        if let Err(e) = result {
+            godot_print!("failed to write {} -> {}", SETTINGS_PATH, e);
+        }
+    }
+
+    ///
+    /// "volume.bgm" のように、ドットで区切った名前で設定を変える
+    ///
+    #[export]
+    fn set_setting(&self, owner: &Node, key: String, value: Variant) -> bool {
+        let value = match setting_value_from_variant(&value) {
+            Some(value) => value,
+            None => {
+                godot_print!("unsupported value for setting {}", key);
+                return false;
+            }
+        };
+
+        let changed = control_settings_mut(|settings| {
+            let changed = settings.set(&key, value)?;
+            if changed {
+                Self::save_settings(settings);
+            }
+            Ok::<_, SettingsError>(changed)
+        });
+
+        match changed {
+            Ok(true) => {
+                Self::apply(owner, &key);
+
+                let value = self.get_setting(owner, key.clone());
+                owner.emit_signal("setting_changed", &[Variant::from_str(&key), value]);
+                true
+            }
+            Ok(false) => true,
+            Err(e) => {
+                godot_print!("{}", e);
+                false
+            }
+        }
+    }
+
+    ///
+    /// 存在しない設定の場合はnullを返す
+    ///
+    #[export]
+    fn get_setting(&self, _owner: &Node, key: String) -> Variant {
+        control_settings(|settings| settings.get(&key))
+            .map(|value| setting_value_to_variant(&value))
+            .unwrap_or_default()
+    }
+
+    ///
+    /// すべて既定値に戻す。setting_changedのkeyは空文字列になる
+    ///
+    #[export]
+    fn reset_settings(&self, owner: &Node) {
+        let settings = GameSettings::default();
+        Self::save_settings(&settings);
+        control_settings_mut(|current| *current = settings);
+
+        for key in ENGINE_SETTINGS {
+            Self::apply(owner, key);
+        }
+        owner.emit_signal("setting_changed", &[Variant::from_str(""), Variant::new()]);
+    }
+
+    ///
+    /// エンジン側の状態に反映する
+    /// テキスト表示の速さなど、各ノードが持っている設定はsetting_changedを受けて各ノードが反映する
+    ///
+    fn apply(owner: &Node, key: &str) {
+        let settings = control_settings(|settings| settings.clone());
+
+        match key.split('.').next().unwrap_or_default() {
+            "window_mode" => {
+                let os = OS::godot_singleton();
+                os.set_window_fullscreen(settings.window_mode == WindowMode::Fullscreen);
+                os.set_borderless_window(settings.window_mode == WindowMode::Borderless);
+            }
+            "volume" => Self::apply_volumes(&settings.volume),
+            "language" => {
+                TranslationServer::godot_singleton().set_locale(settings.language.as_str())
+            }
+            "key_bindings" => Self::apply_key_bindings(&settings),
+            "autosave_policy" => {
+                if let Some(save_data_manager) = owner.get_node("/root/SaveDataManager") {
+                    unsafe {
+                        save_data_manager.assume_safe().call(
+                            "set_autosave_policy",
+                            &[Variant::from_str(settings.autosave_policy.to_string())],
+                        );
+                    }
+                }
+            }
+            "save_format" => {
+                if let Some(save_data_manager) = owner.get_node("/root/SaveDataManager") {
+                    unsafe {
+                        save_data_manager.assume_safe().call(
+                            "set_save_format",
+                            &[Variant::from_str(settings.save_format.to_string())],
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn apply_volumes(volumes: &Volumes) {
+        let audio_server = AudioServer::godot_singleton();
+
+        for (bus, volume) in VOLUME_BUSES {
+            let index = audio_server.get_bus_index(bus);
+            if index < 0 {
+                continue;
+            }
+
+            let volume = volume(volumes);
+            let db = if volume > 0.0 {
+                (20.0 * volume.log10()).max(SILENT_DB)
+            } else {
+                SILENT_DB
+            };
+            audio_server.set_bus_volume_db(index, db);
+        }
+    }
+
+    fn apply_key_bindings(settings: &GameSettings) {
+        let input_map = InputMap::godot_singleton();
+        let os = OS::godot_singleton();
+
+        for (action, keys) in &settings.key_bindings {
+            if !input_map.has_action(action.as_str()) {
+                godot_print!("unknown input action in settings: {}", action);
+                continue;
+            }
+
+            input_map.action_erase_events(action.as_str());
+            for key in keys {
+                let scancode = os.find_scancode_from_string(key.as_str());
+                if scancode == 0 {
+                    godot_print!("unknown key name in settings: {}", key);
+                    continue;
+                }
+
+                let event = InputEventKey::new();
+                event.set_scancode(scancode);
+                input_map.action_add_event(action.as_str(), event);
+            }
+        }
+    }
+}
+
+///
+/// bool, int, float, String と、それらの配列を設定の値にする
+///
+pub fn setting_value_from_variant(value: &Variant) -> Option<toml::Value> {
+    match value.get_type() {
+        VariantType::Bool => value.try_to_bool().map(toml::Value::Boolean),
+        VariantType::I64 => value.try_to_i64().map(toml::Value::Integer),
+        VariantType::F64 => value.try_to_f64().map(toml::Value::Float),
+        VariantType::GodotString => value
+            .try_to_godot_string()
+            .map(|s| toml::Value::String(s.to_string())),
+        VariantType::VariantArray => value.try_to_array().and_then(|array| {
+            array
+                .iter()
+                .map(|value| setting_value_from_variant(&value))
+                .collect::<Option<Vec<_>>>()
+                .map(toml::Value::Array)
+        }),
+        _ => None,
+    }
+}
+
+pub fn setting_value_to_variant(value: &toml::Value) -> Variant {
+    match value {
+        toml::Value::Boolean(b) => Variant::from_bool(*b),
+        toml::Value::Integer(n) => Variant::from_i64(*n),
+        toml::Value::Float(f) => Variant::from_f64(*f),
+        toml::Value::String(s) => Variant::from_str(s),
+        toml::Value::Datetime(d) => Variant::from_str(d.to_string()),
+        toml::Value::Array(values) => {
+            let array = VariantArray::new();
+            for value in values {
+                array.push(setting_value_to_variant(value));
+            }
+            array.into_shared().to_variant()
+        }
+        toml::Value::Table(table) => {
+            let dict = Dictionary::new();
+            for (key, value) in table {
+                dict.insert(key.as_str(), setting_value_to_variant(value));
+            }
+            dict.into_shared().to_variant()
+        }
+    }
+}
+
+pub fn control_settings<F, R>(f: F) -> R
+where
+    F: FnOnce(&GameSettings) -> R,
+{
+    control_settings_mut(|settings| f(settings))
+}
+
+pub fn control_settings_mut<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut GameSettings) -> R,
+{
+    f(CURRENT_SETTINGS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(SettingsManager::load_settings))
+}
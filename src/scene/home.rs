@@ -3,7 +3,15 @@ use gdnative::{
     prelude::*,
 };
 
-use crate::{get_node_auto, native_lib::save_data::{Item, SaveDataManager, SoilItem, control_save_data, control_save_data_mut}};
+use crate::{
+    get_node_auto,
+    native_lib::{
+        date_from_variant, instance_scene, load_scene,
+        save_data::{autosave, control_save_data, item, try_control_save_data, SaveDataManager},
+        settings,
+    },
+    utils::{confirm_dialog::CONFIRM_DIALOG_GROUP, find_node_in_group, toast::TOAST_GROUP},
+};
 
 #[derive(NativeClass)]
 #[inherit(Node2D)]
@@ -16,10 +24,38 @@ impl Calendar {
     }
 
     #[export]
-    fn _ready(&self, owner: &Node2D) {
+    fn _ready(&self, owner: TRef<Node2D>) {
         godot_print!("Calendar ready");
-        self.set_month(owner, 1);
-        self.set_day(owner, 1);
+
+        match try_control_save_data(|save_data| *save_data.get_date()) {
+            Ok(date) => {
+                self.set_month(&owner, date.month as i32);
+                self.set_day(&owner, date.day as i32);
+            }
+            Err(_) => {
+                self.set_month(&owner, 1);
+                self.set_day(&owner, 1);
+            }
+        }
+
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        save_data_manager
+            .connect(
+                "date_changed",
+                owner,
+                "date_changed_handler",
+                VariantArray::new_shared(),
+                0,
+            )
+            .unwrap();
+    }
+
+    #[export]
+    fn date_changed_handler(&self, owner: &Node2D, date: Variant) {
+        if let Some(date) = date_from_variant(&date) {
+            self.set_month(owner, date.month as i32);
+            self.set_day(owner, date.day as i32);
+        }
     }
 
     #[export]
@@ -115,14 +151,39 @@ impl MagicBoardHome {
             )
             .unwrap();
 
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        for signal in ["date_changed", "time_changed"] {
+            save_data_manager
+                .connect(
+                    signal,
+                    owner,
+                    "save_data_changed_handler",
+                    VariantArray::new_shared(),
+                    0,
+                )
+                .unwrap();
+        }
+
+        self.update_date(owner);
+    }
+
+    #[export]
+    fn update_date(&self, owner: TRef<Node2D>) {
         let date = get_node_auto!(owner, "Date", Label);
         control_save_data(|save_data| {
-            date.set_text(GodotString::from_str(
+            date.set_text(GodotString::from_str(format!(
+                "{} {}",
                 save_data.get_date().to_short_string(),
-            ));
+                save_data.get_time()
+            )));
         });
     }
 
+    #[export]
+    fn save_data_changed_handler(&self, owner: TRef<Node2D>, _value: Variant) {
+        self.update_date(owner);
+    }
+
     #[export]
     fn profile_pressed(&self, owner: &Node2D) {
         owner.emit_signal(
@@ -214,10 +275,9 @@ impl MagicBoard {
                 "move_mb_contents_handler",
                 VariantArray::new_shared(),
                 0,
-            
-            ).unwrap();
+            )
+            .unwrap();
 
-        
         let guide_book = get_node_auto!(owner, "Background/GuideBook", Node2D);
         guide_book
             .connect(
@@ -228,6 +288,54 @@ impl MagicBoard {
                 0,
             )
             .unwrap();
+
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        for (signal, method) in [
+            ("save_completed", "save_completed_handler"),
+            ("save_failed", "save_failed_handler"),
+            ("load_completed", "load_completed_handler"),
+            ("load_failed", "load_failed_handler"),
+        ] {
+            save_data_manager
+                .connect(signal, owner, method, VariantArray::new_shared(), 0)
+                .unwrap();
+        }
+    }
+
+    fn show_toast(&self, owner: TRef<Node2D>, message: String, success: bool) {
+        if let Some(toast) = find_node_in_group(&owner, TOAST_GROUP) {
+            unsafe {
+                toast.call(
+                    "show_message",
+                    &[Variant::from_str(message), Variant::from_bool(success)],
+                );
+            }
+        }
+    }
+
+    #[export]
+    fn save_completed_handler(&self, owner: TRef<Node2D>, slot: GodotString) {
+        // オートセーブはプレイヤーの操作ではないので通知しない
+        if autosave::is_reserved_slot(&slot.to_string()) {
+            return;
+        }
+
+        self.show_toast(owner, "セーブしました".to_string(), true);
+    }
+
+    #[export]
+    fn save_failed_handler(&self, owner: TRef<Node2D>, _slot: GodotString, reason: GodotString) {
+        self.show_toast(owner, format!("セーブに失敗しました: {}", reason), false);
+    }
+
+    #[export]
+    fn load_completed_handler(&self, owner: TRef<Node2D>, _slot: GodotString) {
+        self.show_toast(owner, "ロードしました".to_string(), true);
+    }
+
+    #[export]
+    fn load_failed_handler(&self, owner: TRef<Node2D>, _slot: GodotString, reason: GodotString) {
+        self.show_toast(owner, format!("ロードに失敗しました: {}", reason), false);
     }
 
     fn set_child_node_visibility(&self, owner: &Node2D, name: &str, visible: bool) {
@@ -453,17 +561,26 @@ impl MBSaveApp {
 #[inherit(Node2D)]
 pub struct SaveEntry {
     save_mode: bool,
+    empty: bool,
 }
 
 #[methods]
 impl SaveEntry {
     fn new(_owner: &Node2D) -> Self {
-        SaveEntry { save_mode: true }
+        SaveEntry {
+            save_mode: true,
+            empty: true,
+        }
     }
 
     #[export]
-    fn set_mode(&mut self, _owner: TRef<Node2D>, save_mode: Variant) {
+    fn set_mode(&mut self, owner: TRef<Node2D>, save_mode: Variant) {
         self.save_mode = save_mode.to_bool();
+
+        // オートセーブのエントリには手動でセーブできない
+        let action = get_node_auto!(owner, "Button", Button);
+        action
+            .set_disabled(self.save_mode && autosave::is_reserved_slot(&owner.name().to_string()));
     }
 
     #[export]
@@ -482,86 +599,366 @@ impl SaveEntry {
                 0,
             )
             .unwrap();
+
+        // 削除、複製、名前の変更はシーンにボタンがある場合だけ使える
+        for (path, method) in [
+            ("Delete", "delete_button_pressed"),
+            ("Copy", "copy_button_pressed"),
+        ] {
+            if let Some(button) = owner.get_node(path) {
+                unsafe { button.assume_safe() }
+                    .connect("pressed", owner, method, VariantArray::new_shared(), 0)
+                    .unwrap();
+            }
+        }
+
+        if let Some(name_edit) = owner.get_node("NameEdit") {
+            unsafe { name_edit.assume_safe() }
+                .connect(
+                    "text_entered",
+                    owner,
+                    "name_entered",
+                    VariantArray::new_shared(),
+                    0,
+                )
+                .unwrap();
+        }
+
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        save_data_manager
+            .connect(
+                "save_completed",
+                owner,
+                "save_completed_handler",
+                VariantArray::new_shared(),
+                0,
+            )
+            .unwrap();
     }
 
+    ///
+    /// セーブはバックグラウンドで行われるので、書き込みが終わってから表示を更新する
+    ///
     #[export]
-    fn action_button_pressed(&self, owner: TRef<Node2D>) {
-        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+    fn save_completed_handler(&mut self, owner: TRef<Node2D>, slot: GodotString) {
+        if SaveDataManager::entry_slot(&owner.name().to_string()).ok() == Some(slot.to_string()) {
+            self.update(owner);
+        }
+    }
 
+    #[export]
+    fn action_button_pressed(&self, owner: TRef<Node2D>) {
         if self.save_mode {
-            unsafe {
-                save_data_manager.call("save", &[Variant::from_godot_string(&owner.name())]);
+            if autosave::is_reserved_slot(&owner.name().to_string()) {
+                return;
+            }
+
+            if self.empty || !self.ask(owner, "このスロットに上書きしますか？", "save")
+            {
+                self.call_save_data_manager(owner, "save");
             }
-            self.update(owner);
         } else {
-            unsafe {
-                save_data_manager.call("load", &[Variant::from_godot_string(&owner.name())]);
+            let dirty = try_control_save_data(|save_data| save_data.is_dirty()).unwrap_or(false);
+
+            if !dirty
+                || !self.ask(
+                    owner,
+                    "セーブしていない進行状況は失われます。ロードしますか？",
+                    "load",
+                )
+            {
+                self.call_save_data_manager(owner, "load");
             }
         }
     }
 
+    fn call_save_data_manager(&self, owner: TRef<Node2D>, method: &str) {
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        unsafe {
+            save_data_manager.call(method, &[Variant::from_godot_string(&owner.name())]);
+        }
+    }
+
+    fn confirm_tag(owner: TRef<Node2D>, action: &str) -> String {
+        format!("{}:{}", action, owner.name())
+    }
+
+    ///
+    /// 確認ダイアログを出す。ダイアログがシーンに無い場合はfalseを返すので、確認せずに実行する
+    ///
+    fn ask(&self, owner: TRef<Node2D>, message: &str, action: &str) -> bool {
+        let dialog = match find_node_in_group(&owner, CONFIRM_DIALOG_GROUP) {
+            Some(dialog) => dialog,
+            None => return false,
+        };
+
+        if !dialog.is_connected("confirmed", owner, "confirmed_handler") {
+            dialog
+                .connect(
+                    "confirmed",
+                    owner,
+                    "confirmed_handler",
+                    VariantArray::new_shared(),
+                    0,
+                )
+                .unwrap();
+        }
+
+        unsafe {
+            dialog.call(
+                "ask",
+                &[
+                    Variant::from_str(message),
+                    Variant::from_str(Self::confirm_tag(owner, action)),
+                ],
+            );
+        }
+
+        true
+    }
+
     #[export]
-    fn update(&self, owner: TRef<Node2D>) {
+    fn confirmed_handler(&self, owner: TRef<Node2D>, tag: Variant) {
+        let tag = tag.to_string();
+
+        for action in ["save", "load"] {
+            if tag == Self::confirm_tag(owner, action) {
+                self.call_save_data_manager(owner, action);
+            }
+        }
+    }
+
+    #[export]
+    fn delete_button_pressed(&self, owner: TRef<Node2D>) {
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        unsafe {
+            save_data_manager.call("delete_slot", &[Variant::from_godot_string(&owner.name())]);
+        }
+    }
+
+    #[export]
+    fn copy_button_pressed(&self, owner: TRef<Node2D>) {
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        unsafe {
+            save_data_manager.call(
+                "duplicate_slot",
+                &[Variant::from_godot_string(&owner.name())],
+            );
+        }
+    }
+
+    #[export]
+    fn name_entered(&self, owner: TRef<Node2D>, name: GodotString) {
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        unsafe {
+            save_data_manager.call(
+                "rename_slot",
+                &[
+                    Variant::from_godot_string(&owner.name()),
+                    Variant::from_godot_string(&name),
+                ],
+            );
+        }
+    }
+
+    #[export]
+    fn update(&mut self, owner: TRef<Node2D>) {
+        let entry = owner.name().to_string();
+        let (slot, summary) = match SaveDataManager::entry_slot(&entry) {
+            Ok(slot) => {
+                let summary = SaveDataManager::load_slot_summary(&slot);
+                (slot, summary)
+            }
+            Err(e) => (entry, Err(e)),
+        };
+        let name = SaveDataManager::slot_display_name(&slot);
+
+        self.set_summary(
+            owner,
+            SaveDataManager::slot_summary_to_dictionary(&slot, &name, &summary)
+                .into_shared()
+                .to_variant(),
+        );
+    }
+
+    #[export]
+    fn set_summary(&mut self, owner: TRef<Node2D>, summary: Variant) {
         let name = get_node_auto!(owner, "Button/Name", Label);
         let date = get_node_auto!(owner, "Button/Date", Label);
 
-        if let Some(save_data) = SaveDataManager::load_native_save_data(owner.name()) {
-            date.set_text(save_data.get_real_date().to_string());
-            name.set_text(&format!("{}", owner.name()));
-        } else {
+        let summary = summary.to_dictionary();
+        self.empty = summary.get("empty").to_bool();
+
+        if self.empty {
             name.set_text("空");
+        } else {
+            date.set_text(format!(
+                "{} {}",
+                summary.get("real_date").to_string(),
+                summary.get("playtime").to_string()
+            ));
+            name.set_text(summary.get("name").to_string());
         }
     }
 }
 
 #[derive(NativeClass)]
 #[inherit(Node2D)]
-pub struct SaveDataSet;
+pub struct SaveDataSet {
+    save_mode: bool,
+    page: usize,
+}
 
 #[methods]
 impl SaveDataSet {
+    /// SaveEntryの行として実体化するシーン
+    const SAVE_ENTRY_SCENE: &'static str = "res://scene/home/SaveEntry.tscn";
+    const ENTRIES_PER_PAGE: usize = 6;
+    const ENTRY_HEIGHT: f32 = 64.0;
+
     fn new(_owner: &Node2D) -> Self {
-        SaveDataSet
+        SaveDataSet {
+            save_mode: true,
+            page: 0,
+        }
     }
 
     #[export]
-    fn _ready(&self, _owner: TRef<Node2D>) {
+    fn _ready(&self, owner: TRef<Node2D>) {
         godot_print!("SaveDataSet ready");
+
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        save_data_manager
+            .connect(
+                "slots_changed",
+                owner,
+                "update",
+                VariantArray::new_shared(),
+                0,
+            )
+            .unwrap();
+
+        for (path, method) in [
+            ("PageButtonContainer/Next", "next_button_pressed"),
+            ("PageButtonContainer/Prev", "prev_button_pressed"),
+            ("NewSlot", "new_slot_pressed"),
+        ] {
+            if let Some(button) = owner.get_node(path) {
+                unsafe { button.assume_safe() }
+                    .connect("pressed", owner, method, VariantArray::new_shared(), 0)
+                    .unwrap();
+            }
+        }
     }
 
     #[export]
-    fn set_mode(&self, owner: &Node2D, save_mode: Variant) {
-        for entry in [
-            "VBox/Entry1",
-            "VBox/Entry2",
-            "VBox/Entry3",
-            "VBox/Entry4",
-            "VBox/Entry5",
-            "VBox/Entry6",
-        ] {
-            let node = get_node_auto!(owner, entry, Node2D);
-            unsafe {
-                node.call("set_mode", &[save_mode.clone()]);
+    fn set_mode(&mut self, owner: TRef<Node2D>, save_mode: Variant) {
+        self.save_mode = save_mode.to_bool();
+
+        // 新しいスロットはセーブするときだけ作れる
+        if let Some(new_slot) = owner.get_node("NewSlot") {
+            if let Some(new_slot) = unsafe { new_slot.assume_safe() }.cast::<CanvasItem>() {
+                new_slot.set_visible(self.save_mode);
             }
         }
+
+        self.update(owner);
+    }
+
+    fn pages(slots: usize) -> usize {
+        slots.max(1).div_ceil(Self::ENTRIES_PER_PAGE)
     }
 
     #[export]
-    fn update(&self, owner: TRef<Node2D>) {
-        for entry in [
-            "VBox/Entry1",
-            "VBox/Entry2",
-            "VBox/Entry3",
-            "VBox/Entry4",
-            "VBox/Entry5",
-            "VBox/Entry6",
-        ] {
-            let node = get_node_auto!(owner, entry, Node2D);
+    fn update(&mut self, owner: TRef<Node2D>) {
+        let vbox = get_node_auto!(owner, "VBox", Node);
+
+        // 名前を再利用するので、queue_freeの前に取り外しておく
+        for child in vbox.get_children().iter() {
+            if let Some(child) = child.try_to_object::<Node>() {
+                let child = unsafe { child.assume_safe() };
+                vbox.remove_child(child);
+                child.queue_free();
+            }
+        }
+
+        // 各エントリで個別に読み込まず、まとめてヘッダだけを読む
+        let slots = SaveDataManager::load_all_slot_summaries();
+        self.page = self.page.min(Self::pages(slots.len()) - 1);
+
+        let scene = match load_scene(Self::SAVE_ENTRY_SCENE) {
+            Some(scene) => scene,
+            None => {
+                godot_print!("failed to load {}", Self::SAVE_ENTRY_SCENE);
+                return;
+            }
+        };
+
+        for (row, (entry, summary)) in slots
+            .iter()
+            .skip(self.page * Self::ENTRIES_PER_PAGE)
+            .take(Self::ENTRIES_PER_PAGE)
+            .enumerate()
+        {
+            let node = match instance_scene::<Node2D>(&scene) {
+                Ok(node) => node,
+                Err(e) => {
+                    godot_print!("failed to instance {} -> {:?}", Self::SAVE_ENTRY_SCENE, e);
+                    return;
+                }
+            };
+
+            // SaveEntryはノード名をスロットのidとして使う
+            node.set_name(entry.id.as_str());
+            node.set_position(Vector2::new(0.0, row as f32 * Self::ENTRY_HEIGHT));
+
+            let node = node.into_shared();
+            vbox.add_child(node, false);
+
+            let node = unsafe { node.assume_safe() };
+            let summary =
+                SaveDataManager::slot_summary_to_dictionary(&entry.id, &entry.name, summary);
             unsafe {
-                node.call("update", &[]);
+                node.call("set_mode", &[Variant::from_bool(self.save_mode)]);
+                node.call("set_summary", &[summary.into_shared().to_variant()]);
+            }
+        }
+
+        if let Some(page_number) = owner.get_node("PageNumber") {
+            if let Some(page_number) = unsafe { page_number.assume_safe() }.cast::<Label>() {
+                page_number.set_text(format!("{}/{}", self.page + 1, Self::pages(slots.len())));
+            }
+        }
+
+        // オートセーブは手動のスロットとは別に、一番新しいものだけを表示する
+        if let Some(node) = owner.get_node(autosave::AUTOSAVE_ENTRY) {
+            unsafe {
+                node.assume_safe()
+                    .call("set_mode", &[Variant::from_bool(self.save_mode)]);
+                node.assume_safe().call("update", &[]);
             }
         }
     }
+
+    #[export]
+    fn next_button_pressed(&mut self, owner: TRef<Node2D>) {
+        self.page += 1;
+        self.update(owner);
+    }
+
+    #[export]
+    fn prev_button_pressed(&mut self, owner: TRef<Node2D>) {
+        self.page = self.page.saturating_sub(1);
+        self.update(owner);
+    }
+
+    #[export]
+    fn new_slot_pressed(&mut self, owner: TRef<Node2D>) {
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        unsafe {
+            save_data_manager.call("save_to_new_slot", &[]);
+        }
+    }
 }
 
 #[derive(NativeClass)]
@@ -741,6 +1138,22 @@ impl MBItemList {
             )
             .unwrap();
 
+        let save_data_manager = get_node_auto!(owner, "/root/SaveDataManager", Node);
+        save_data_manager
+            .connect(
+                "items_changed",
+                owner,
+                "items_changed_handler",
+                VariantArray::new_shared(),
+                0,
+            )
+            .unwrap();
+
+        self.update_item_list(owner);
+    }
+
+    #[export]
+    fn items_changed_handler(&self, owner: TRef<Node2D>) {
         self.update_item_list(owner);
     }
 
@@ -757,16 +1170,19 @@ impl MBItemList {
 
     fn update_item_list(&self, owner: TRef<Node2D>) {
         self.hide_all_item_entries(owner);
-        control_save_data_mut(|save_data| {
-            save_data.add_items(Item::Soil(SoilItem::Fuyodo), 2);
+        let language = settings::control_settings(|settings| settings.language.clone());
+
+        control_save_data(|save_data| {
             for (line, data) in save_data.get_items().iter().enumerate().take(6) {
                 let key_str = format!("WholeVBox/ItemListVBox/Line{}", line + 1);
                 let item_entry = get_node_auto!(owner, key_str.as_str(), Container);
 
                 item_entry.show();
 
+                let name =
+                    item::with_item_catalog(|catalog| catalog.display_name(data.0, &language));
                 unsafe {
-                    item_entry.call("set_name", &[Variant::from_str(data.0.get_display_name())]);
+                    item_entry.call("set_name", &[Variant::from_str(name)]);
                     item_entry.call("set_count", &[Variant::from_u64(*data.1 as u64)]);
                 }
             }
@@ -815,7 +1231,6 @@ impl MBItemList {
     }
 }
 
-
 #[derive(NativeClass)]
 #[inherit(Node2D)]
 #[register_with(Self::register_signals)]
@@ -864,10 +1279,7 @@ impl GuideBook {
     pub fn back_button_pressed(&mut self, owner: TRef<Node2D>) {
         owner.emit_signal(
             "move_mb_contents",
-            &[
-                Variant::from_str("GuideBook"),
-                Variant::from_str("Home"),
-            ],
+            &[Variant::from_str("GuideBook"), Variant::from_str("Home")],
         );
     }
-}
\ No newline at end of file
+}
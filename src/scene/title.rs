@@ -74,6 +74,27 @@ impl TitleEntries {
             )
             .unwrap();
 
+        // 続きから: 一番最近のセーブをロードし、ロード後はセーブしたときのシーンへ移る
+        if let Some(continue_button) = owner.get_node("Main/Continue") {
+            let continue_button = unsafe { continue_button.assume_safe() };
+            let save_data_manager = get_node_assume_safe!(owner, "/root/SaveDataManager");
+            let latest = unsafe { save_data_manager.call("get_latest_slot", &[]) };
+
+            if let Some(button) = continue_button.cast::<Button>() {
+                button.set_disabled(latest.to_string().is_empty());
+            }
+
+            continue_button
+                .connect(
+                    "pressed",
+                    owner,
+                    "main_continue_pressed",
+                    VariantArray::new_shared(),
+                    0,
+                )
+                .unwrap();
+        }
+
         let emitter = owner.get_node("Main/Exit").unwrap();
         let emitter = unsafe { emitter.assume_safe() };
 
@@ -96,6 +117,18 @@ impl TitleEntries {
         crate::goto_scene(owner, "res://scene/home/Home.tscn");
     }
 
+    #[export]
+    fn main_continue_pressed(&mut self, owner: &Node2D) {
+        let save_data_manager = get_node_assume_safe!(owner, "/root/SaveDataManager");
+
+        unsafe {
+            let latest = save_data_manager.call("get_latest_slot", &[]);
+            if !latest.to_string().is_empty() {
+                save_data_manager.call("load", &[latest]);
+            }
+        }
+    }
+
     #[export]
     fn main_exit_pressed(&mut self, owner: &Node2D) {
         crate::quit_game(owner.get_node("/root/Global").unwrap());
@@ -1,6 +1,11 @@
+pub mod confirm_dialog;
 pub mod textbox;
+pub mod toast;
 
-use gdnative::{api::{AnimationPlayer, TextureRect}, prelude::*};
+use gdnative::{
+    api::{AnimationPlayer, TextureRect},
+    prelude::*,
+};
 
 use crate::get_node_auto;
 
@@ -53,20 +58,59 @@ impl TextureDigit {
     }
 }
 
-
 #[derive(NativeClass)]
 #[inherit(Node2D)]
-pub struct SceneTransition;
+pub struct SceneTransition {
+    /// Showのアニメーションが終わったら移るシーン
+    pending_scene: Option<String>,
+}
+
+/// 自分自身を登録するグループ。find_node_in_groupで探す
+pub const SCENE_TRANSITION_GROUP: &str = "scene_transition";
 
 #[methods]
 impl SceneTransition {
     fn new(_owner: &Node2D) -> Self {
-        SceneTransition
+        SceneTransition {
+            pending_scene: None,
+        }
     }
 
     #[export]
-    fn _ready(&self, _owner: &Node2D) {
+    fn _ready(&self, owner: TRef<Node2D>) {
         godot_print!("SceneTransition ready");
+        owner.add_to_group(SCENE_TRANSITION_GROUP, false);
+
+        get_node_auto!(owner, "Texture/AnimationPlayer", AnimationPlayer)
+            .connect(
+                "animation_finished",
+                owner,
+                "animation_finished_handler",
+                VariantArray::new_shared(),
+                0,
+            )
+            .unwrap();
+    }
+
+    ///
+    /// 画面を隠してからシーンを切り替え、切り替えた後に画面を戻す
+    ///
+    #[export]
+    fn transition_to(&mut self, owner: &Node2D, path: String) {
+        self.pending_scene = Some(path);
+        self.start_show_trans_anime(owner);
+    }
+
+    #[export]
+    fn animation_finished_handler(&mut self, owner: &Node2D, name: GodotString) {
+        if name.to_string() != "Show" {
+            return;
+        }
+
+        if let Some(path) = self.pending_scene.take() {
+            crate::change_scene(owner, &path);
+            self.start_hide_trans_anime(owner);
+        }
     }
 
     #[export]
@@ -80,4 +124,21 @@ impl SceneTransition {
         get_node_auto!(owner, "Texture/AnimationPlayer", AnimationPlayer)
             .play("Hide", -1.0, 1.0, false);
     }
-}
\ No newline at end of file
+}
+
+///
+/// グループに登録されている最初のノードを返す
+/// ConfirmDialogやToastのように、シーン内のどこに置かれていてもよいノードを探すのに使う
+///
+pub fn find_node_in_group<'a>(owner: &'a Node, group: &str) -> Option<TRef<'a, Node>> {
+    let tree = owner.get_tree()?;
+    let tree = unsafe { tree.assume_safe() };
+
+    let nodes = tree.get_nodes_in_group(group);
+    if nodes.is_empty() {
+        return None;
+    }
+    let node = nodes.get(0).try_to_object::<Node>()?;
+
+    Some(unsafe { node.assume_safe() })
+}
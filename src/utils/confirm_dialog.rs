@@ -0,0 +1,88 @@
+use gdnative::prelude::*;
+
+use crate::get_node_auto;
+
+/// 自分自身を登録するグループ。find_node_in_groupで探す
+pub const CONFIRM_DIALOG_GROUP: &str = "confirm_dialog";
+
+///
+/// はい/いいえで確認を取るダイアログ
+/// 呼び出し側はaskにtagを渡し、confirmed/cancelledで返ってくるtagで自分の問い合わせかを判断する
+///
+#[derive(NativeClass)]
+#[inherit(Node2D)]
+#[register_with(Self::register_signals)]
+pub struct ConfirmDialog {
+    tag: Option<Variant>,
+}
+
+#[methods]
+impl ConfirmDialog {
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        for name in ["confirmed", "cancelled"] {
+            builder.add_signal(Signal {
+                name,
+                args: &[SignalArgument {
+                    name: "tag",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Nil),
+                    usage: PropertyUsage::DEFAULT,
+                }],
+            });
+        }
+    }
+
+    fn new(_owner: &Node2D) -> Self {
+        ConfirmDialog { tag: None }
+    }
+
+    #[export]
+    fn _ready(&self, owner: TRef<Node2D>) {
+        godot_print!("ConfirmDialog ready");
+        owner.add_to_group(CONFIRM_DIALOG_GROUP, false);
+        owner.hide();
+
+        for (path, method) in [("Yes", "yes_pressed"), ("No", "no_pressed")] {
+            get_node_auto!(owner, path, Button)
+                .connect("pressed", owner, method, VariantArray::new_shared(), 0)
+                .unwrap();
+        }
+    }
+
+    ///
+    /// 確認中に別の問い合わせが来た場合は、前の問い合わせをキャンセル扱いにする
+    ///
+    #[export]
+    fn ask(&mut self, owner: TRef<Node2D>, message: String, tag: Variant) {
+        if let Some(previous) = self.tag.take() {
+            owner.emit_signal("cancelled", &[previous]);
+        }
+
+        get_node_auto!(owner, "Message", Label).set_text(message);
+        self.tag = Some(tag);
+        owner.show();
+    }
+
+    #[export]
+    fn is_asking(&self, _owner: &Node2D) -> bool {
+        self.tag.is_some()
+    }
+
+    #[export]
+    fn yes_pressed(&mut self, owner: &Node2D) {
+        self.answer(owner, "confirmed");
+    }
+
+    #[export]
+    fn no_pressed(&mut self, owner: &Node2D) {
+        self.answer(owner, "cancelled");
+    }
+
+    fn answer(&mut self, owner: &Node2D, signal: &str) {
+        owner.hide();
+
+        if let Some(tag) = self.tag.take() {
+            owner.emit_signal(signal, &[tag]);
+        }
+    }
+}
@@ -1,97 +1,38 @@
-use gdnative::{
-    api::{File, RichTextLabel},
-    prelude::*,
-};
-
-use crate::get_node_auto;
-
-pub struct Serif {
-    speaker: String,
-    lines: Vec<String>,
-    current_line: usize,
-}
-
-impl Serif {
-    pub fn new(speaker: String, lines: Vec<String>) -> Self {
-        Serif {
-            speaker: speaker,
-            lines: lines,
-            current_line: 0,
-        }
-    }
-
-    pub fn current_line_len(&self) -> usize {
-        let index = self.current_line % self.lines.len();
-        self.lines[index].len()
-    }
+use gdnative::{api::RichTextLabel, prelude::*};
 
-    pub fn current_line(&self) -> &str {
-        let index = self.current_line % self.lines.len();
-        self.lines.get(index).unwrap()
-    }
-
-    pub fn next_line(&mut self) {
-        self.current_line += 1;
-    }
-
-    pub fn finish(&self) -> bool {
-        self.lines.len() <= self.current_line
-    }
-
-    pub fn get_speaker_name(&self) -> &str {
-        self.speaker.as_str()
-    }
-}
+use yuka_core::dialogue::Dialogue;
 
-pub struct Dialogue {
-    text: Vec<Serif>,
-    current_line: usize,
-}
-
-impl Dialogue {
-    pub fn new(dialogue_file_path: String) -> Self {
-        let mut text = Vec::new();
-
-        let f = File::new();
-        f.open(dialogue_file_path, File::READ)
-            .expect("failed open file");
-        let s = f.get_as_text().to_string();
-
-        for line in s.lines() {
-            text.push(Serif::new("スピーカー".to_string(), vec![line.to_string()]));
-        }
-
-        Dialogue {
-            text: text,
-            current_line: 0,
-        }
-    }
-
-    pub fn next_line(&mut self) {
-        self.current_line += 1;
-    }
+use crate::{
+    get_node_auto,
+    native_lib::{
+        read_text_file,
+        save_data::{try_control_save_data, try_control_save_data_mut, DialogueProgress},
+        settings::control_settings,
+    },
+};
 
-    pub fn get_current_serif(&self) -> &Serif {
-        let index = self.current_line % self.text.len();
-        self.text.get(index).unwrap()
-    }
+const DEFAULT_DIALOGUE_PATH: &str = "res://resources/scenario/sample.txt";
 
-    pub fn get_current_serif_mut(&mut self) -> &mut Serif {
-        let index = self.current_line % self.text.len();
-        self.text.get_mut(index).unwrap()
-    }
+/// Timerに0秒は設定できないので、最も速い設定でもこの間隔で表示する
+const MIN_TEXT_INTERVAL_SECS: f64 = 0.01;
 
-    pub fn finish(&self) -> bool {
-        self.text.len() <= self.current_line + 1
-    }
+fn load_dialogue(dialogue_file_path: &str) -> Option<Dialogue> {
+    read_text_file(dialogue_file_path).map(|text| Dialogue::parse(&text))
 }
 
 #[derive(NativeClass)]
 #[inherit(Node2D)]
 pub struct TextBox {
+    dialogue_path: String,
     dialogue: Dialogue,
     seeker: usize,
     current_buffer: String,
+    /// 最後のSerifまで表示し終えた
+    finished: bool,
+    text_interval_secs: f64,
+    auto_advance: bool,
+    auto_advance_delay_secs: f64,
+    auto_advance_elapsed: f64,
 }
 
 #[methods]
@@ -99,21 +40,58 @@ impl TextBox {
     fn new(_owner: &Node2D) -> Self {
         godot_print!("TextBox::new");
 
-        let dialogue = Dialogue::new("res://resources/scenario/sample.txt".to_string());
-        let current_buffer = dialogue.get_current_serif().lines[0].to_string();
+        // 会話の途中でセーブしていた場合は、その続きから始める
+        let progress = try_control_save_data(|save_data| save_data.get_scene().dialogue.clone())
+            .ok()
+            .flatten();
+
+        // セーブデータにあるパスが開けない場合は、既定の会話を最初から始める
+        let resumed = progress.and_then(|progress| match load_dialogue(&progress.path) {
+            Some(dialogue) => Some((progress.path, progress.position, dialogue)),
+            None => {
+                godot_print!("failed to open {}, use default dialogue", progress.path);
+                None
+            }
+        });
+        let (dialogue_path, position, mut dialogue) = resumed.unwrap_or_else(|| {
+            let dialogue = load_dialogue(DEFAULT_DIALOGUE_PATH).unwrap_or_else(|| {
+                godot_print!("failed to open {}", DEFAULT_DIALOGUE_PATH);
+                Dialogue::parse("")
+            });
+            (DEFAULT_DIALOGUE_PATH.to_string(), 0, dialogue)
+        });
+
+        // 条件付きの行は、その時点のフラグで表示するかどうかを決める
+        let flags =
+            try_control_save_data(|save_data| save_data.get_flags().clone()).unwrap_or_default();
+        dialogue.retain_available(&flags);
+        // セーブした位置は取り除く前の位置なので、取り除いた後でも同じSerifから再開できる
+        dialogue.seek(position);
+        // 条件で全ての行が取り除かれた場合は、何も表示せずに終わる
+        let finished = dialogue.is_empty();
+        let current_buffer = dialogue
+            .get_current_serif()
+            .map_or(String::new(), |serif| serif.current_line().to_string());
 
         godot_print!("{}", current_buffer);
 
         TextBox {
-            dialogue: dialogue,
+            dialogue_path,
+            dialogue,
             seeker: 0,
-            current_buffer: current_buffer,
+            current_buffer,
+            finished,
+            text_interval_secs: 0.1,
+            auto_advance: false,
+            auto_advance_delay_secs: 0.0,
+            auto_advance_elapsed: 0.0,
         }
     }
 
     #[export]
-    fn _ready(&self, owner: TRef<Node2D>) {
+    fn _ready(&mut self, owner: TRef<Node2D>) {
         godot_print!("TextBox::_ready");
+        self.load_settings();
 
         //let main_text_timer = get_node_assume_safe!(owner, "Background/MainText/Timer");
         //let main_text_timer = node_cast_assume_unique!(main_text_timer, Timer);
@@ -128,72 +106,145 @@ impl TextBox {
                 0,
             )
             .unwrap();
-        main_text_timer.start(0.1);
+        main_text_timer.start(self.text_interval_secs);
         main_text_timer.set_one_shot(false);
 
-        let name_text = get_node_auto!(owner, "Name", RichTextLabel);
-        name_text.set_bbcode(
-            self.dialogue
-                .get_current_serif()
-                .get_speaker_name()
-                .to_string(),
-        );
+        if self.finished {
+            godot_print!("no available serif");
+            self.stop_text_update(&owner);
+            self.record_progress(false);
+            godot_print!("TextBox::_ready done");
+            return;
+        }
+
+        // 設定画面で変えた表示速度をすぐに反映する
+        if let Some(settings_manager) = owner.get_node("/root/SettingsManager") {
+            unsafe { settings_manager.assume_safe() }
+                .connect(
+                    "setting_changed",
+                    owner,
+                    "setting_changed_handler",
+                    VariantArray::new_shared(),
+                    0,
+                )
+                .unwrap();
+        }
+
+        if let Some(serif) = self.dialogue.get_current_serif() {
+            let name_text = get_node_auto!(owner, "Name", RichTextLabel);
+            name_text.set_bbcode(serif.get_speaker_name().to_string());
+        }
 
         godot_print!("TextBox::_ready done");
     }
 
     #[export]
-    fn _process(&mut self, owner: &Node2D, _delta: f64) {
+    fn _process(&mut self, owner: &Node2D, delta: f64) {
         if Input::godot_singleton().is_action_just_released("ui_accept") {
-            let current_buffer_len = self.current_buffer.chars().count();
+            self.advance(owner);
+            return;
+        }
 
-            // Serif内の一行の表示が完了している？
-            if self.seeker < current_buffer_len {
-                // していないから、最後まで表示する
-                self.seeker = current_buffer_len;
-            } else {
-                // 最後まで行ってるから次の一行にしたい
-                // とりあえずseekerを0にする
-                self.seeker = 0;
-                self.dialogue.get_current_serif_mut().next_line();
-
-                // 現在のSerifの全ての行が終わった？
-                if self.dialogue.get_current_serif().finish() {
-                    // 終わっているので次のSerifを取り出したいが、
-                    // dialogueも最後まで行ってる可能性があるので分岐
-                    if self.dialogue.finish() {
-                        // 最後の行まで行っていたので何もしない。
-                        godot_print!("end");
-
-                        // 0にリセットされていたseekerを末尾に戻す
-                        self.seeker = current_buffer_len;
-
-                        // 更新を中断
-                        self.stop_text_update(owner);
-                    } else {
-                        // まだ次の行がある。
-                        godot_print!("next serif");
-                        self.dialogue.next_line();
-                        self.current_buffer =
-                            self.dialogue.get_current_serif().current_line().to_string();
-                        self.set_speaker_text(
-                            owner,
-                            self.dialogue
-                                .get_current_serif()
-                                .get_speaker_name()
-                                .to_string(),
+        // 自動送り: 一行を表示し終えてから、設定された時間が経ったら次へ進む
+        let line_shown = self.seeker >= self.current_buffer.chars().count();
+        if self.auto_advance && !self.finished && line_shown {
+            self.auto_advance_elapsed += delta;
+            if self.auto_advance_elapsed >= self.auto_advance_delay_secs {
+                self.advance(owner);
+            }
+        }
+    }
+
+    fn advance(&mut self, owner: &Node2D) {
+        self.auto_advance_elapsed = 0.0;
+
+        if self.dialogue.is_empty() {
+            return;
+        }
+
+        let current_buffer_len = self.current_buffer.chars().count();
+
+        // Serif内の一行の表示が完了している？
+        if self.seeker < current_buffer_len {
+            // していないから、最後まで表示する
+            self.seeker = current_buffer_len;
+        } else {
+            // 最後まで行ってるから次の一行にしたい
+            // とりあえずseekerを0にする
+            self.seeker = 0;
+            if let Some(serif) = self.dialogue.get_current_serif_mut() {
+                serif.next_line();
+            }
+
+            // 現在のSerifの全ての行が終わった？
+            let serif_finished = self
+                .dialogue
+                .get_current_serif()
+                .map_or(true, |serif| serif.finish());
+            if serif_finished {
+                // 終わっているので次のSerifを取り出したいが、
+                // dialogueも最後まで行ってる可能性があるので分岐
+                if self.dialogue.finish() {
+                    // 最後の行まで行っていたので何もしない。
+                    godot_print!("end");
+
+                    // 0にリセットされていたseekerを末尾に戻す
+                    self.seeker = current_buffer_len;
+
+                    // 更新を中断
+                    self.stop_text_update(owner);
+                    self.record_progress(false);
+                    self.finished = true;
+                } else {
+                    // まだ次の行がある。
+                    godot_print!("next serif");
+                    self.dialogue.next_line();
+                    self.record_progress(true);
+                    if let Some(serif) = self.dialogue.get_current_serif() {
+                        let (line, speaker) = (
+                            serif.current_line().to_string(),
+                            serif.get_speaker_name().to_string(),
                         );
+                        self.current_buffer = line;
+                        self.set_speaker_text(owner, speaker);
                     }
-                } else {
-                    // まだSerifが終わっていないので
-                    // 次の行をロードする
-                    self.current_buffer =
-                        self.dialogue.get_current_serif().current_line().to_string();
                 }
+            } else {
+                // まだSerifが終わっていないので
+                // 次の行をロードする
+                if let Some(serif) = self.dialogue.get_current_serif() {
+                    self.current_buffer = serif.current_line().to_string();
+                }
+            }
+        }
+    }
+
+    #[export]
+    fn setting_changed_handler(&mut self, owner: &Node2D, key: GodotString, _value: Variant) {
+        match key.to_string().as_str() {
+            "text_interval_secs" | "auto_advance" | "auto_advance_delay_secs" | "" => {
+                self.load_settings();
+                get_node_auto!(owner, "MainText/Timer", Timer)
+                    .set_wait_time(self.text_interval_secs);
             }
+            _ => (),
         }
     }
 
+    fn load_settings(&mut self) {
+        let (interval, auto_advance, delay) = control_settings(|settings| {
+            (
+                settings.text_interval_secs,
+                settings.auto_advance,
+                settings.auto_advance_delay_secs,
+            )
+        });
+
+        self.text_interval_secs = interval.max(MIN_TEXT_INTERVAL_SECS);
+        self.auto_advance = auto_advance;
+        self.auto_advance_delay_secs = delay;
+    }
+
     #[export]
     fn maintext_timeout(&mut self, owner: &Node2D) {
         if self.current_buffer.chars().count() >= self.seeker {
@@ -207,6 +258,23 @@ impl TextBox {
         self.seeker += 1;
     }
 
+    ///
+    /// 会話の途中でセーブされたときに続きから始められるよう、位置をセーブデータに残す
+    /// 条件で取り除いた行も数えた、会話ファイルの中での位置を残す
+    ///
+    fn record_progress(&self, in_progress: bool) {
+        let progress = if in_progress {
+            Some(DialogueProgress {
+                path: self.dialogue_path.clone(),
+                position: self.dialogue.position(),
+            })
+        } else {
+            None
+        };
+
+        let _ = try_control_save_data_mut(|save_data| save_data.set_dialogue_progress(progress));
+    }
+
     fn set_main_text(&mut self, owner: &Node2D, text: String) {
         let main_text = get_node_auto!(owner, "MainText", RichTextLabel);
 
@@ -0,0 +1,59 @@
+use gdnative::prelude::*;
+
+use crate::get_node_auto;
+
+/// 自分自身を登録するグループ。find_node_in_groupで探す
+pub const TOAST_GROUP: &str = "toast";
+
+///
+/// 操作の結果を短い間だけ表示する
+///
+#[derive(NativeClass)]
+#[inherit(Node2D)]
+pub struct Toast;
+
+#[methods]
+impl Toast {
+    const DURATION_SECS: f64 = 2.0;
+
+    fn new(_owner: &Node2D) -> Self {
+        Toast
+    }
+
+    #[export]
+    fn _ready(&self, owner: TRef<Node2D>) {
+        godot_print!("Toast ready");
+        owner.add_to_group(TOAST_GROUP, false);
+        owner.hide();
+
+        let timer = get_node_auto!(owner, "Timer", Timer);
+        timer.set_one_shot(true);
+        timer
+            .connect("timeout", owner, "timeout", VariantArray::new_shared(), 0)
+            .unwrap();
+    }
+
+    ///
+    /// 表示中に呼ばれた場合は、新しいメッセージに置き換えて表示時間を延ばす
+    ///
+    #[export]
+    fn show_message(&self, owner: TRef<Node2D>, message: String, success: bool) {
+        let label = get_node_auto!(owner, "Message", Label);
+        label.set_text(message);
+
+        let color = if success {
+            Color::rgb(1.0, 1.0, 1.0)
+        } else {
+            Color::rgb(1.0, 0.4, 0.4)
+        };
+        label.add_color_override("font_color", color);
+
+        owner.show();
+        get_node_auto!(owner, "Timer", Timer).start(Self::DURATION_SECS);
+    }
+
+    #[export]
+    fn timeout(&self, owner: &Node2D) {
+        owner.hide();
+    }
+}
@@ -0,0 +1,120 @@
+//!
+//! セーブデータの形式と圧縮方式ごとの、ファイルサイズと書き込み・読み込みにかかる時間の比較
+//!
+//! cargo bench -p yuka-core --bench save_compression
+//!
+//! 畑の区画、既読の会話、イベントの履歴を入れた大きめのセーブデータを使う
+//!
+
+use std::time::{Duration, Instant};
+
+use yuka_core::{
+    calendar::GensoDate,
+    crypt::SaveKeys,
+    flag::FlagValue,
+    item::ItemId,
+    save::{
+        codec::{self, EncodeOptions},
+        compression::Compression,
+        format::SaveFormat,
+        NativeSaveData,
+    },
+};
+
+const ITERATIONS: u32 = 50;
+
+const GARDEN_SIZE: usize = 32;
+const DIALOGUES: usize = 500;
+const EVENTS: usize = 300;
+
+/// ベンチマーク用の鍵。ゲームの鍵とは別のもの
+const BENCH_SECRET: &[u8] = b"yuka-core/benches/save_compression";
+
+fn no_legacy(_: &[u8]) -> Result<String, String> {
+    Err("legacy format is not used in this benchmark".to_string())
+}
+
+fn large_save_data() -> NativeSaveData {
+    let mut save_data = NativeSaveData::new();
+
+    save_data.add_items(ItemId::new("soil.fuyodo"), 12);
+    save_data.add_items(ItemId::new("fertilizer.aburakasu"), 5);
+    save_data.add_items(ItemId::new("fertilizer.gyohi"), 8);
+
+    for x in 0..GARDEN_SIZE {
+        for y in 0..GARDEN_SIZE {
+            let key = format!("garden.plot_{}_{}", x, y);
+            save_data
+                .set_flag(&key, FlagValue::Int(((x * 7 + y * 13) % 5) as i64))
+                .unwrap();
+        }
+    }
+
+    for n in 0..DIALOGUES {
+        let key = format!("dialogue_read.scenario_{}", n);
+        save_data
+            .set_flag(&key, FlagValue::Bool(n % 3 != 0))
+            .unwrap();
+    }
+
+    let mut date = GensoDate::new(112, 5, 1);
+    for n in 0..EVENTS {
+        let key = format!("event_history.event_{}", n);
+        save_data.set_flag(&key, FlagValue::Date(date)).unwrap();
+        date.add_day(1);
+    }
+
+    save_data
+}
+
+fn measure<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let keys = SaveKeys::derive(BENCH_SECRET);
+    let mut save_data = large_save_data();
+    let plain_len = save_data.to_toml_string().unwrap().len();
+
+    println!(
+        "save data: {} flags, {} bytes of TOML, {} iterations",
+        save_data.get_flags().len(),
+        plain_len,
+        ITERATIONS
+    );
+    println!(
+        "{:<6} {:<10} {:>10} {:>8} {:>12} {:>12}",
+        "format", "compress", "bytes", "ratio", "save", "load"
+    );
+
+    for format in SaveFormat::ALL {
+        for compression in [Compression::None, Compression::Deflate] {
+            let options = EncodeOptions {
+                format,
+                compression,
+            };
+            let encoded = codec::encode_with(&keys, &mut save_data, options).unwrap();
+
+            let save = measure(|| {
+                codec::encode_with(&keys, &mut save_data, options).unwrap();
+            });
+            let load = measure(|| {
+                codec::decode(&keys, &encoded, no_legacy).unwrap();
+            });
+
+            println!(
+                "{:<6} {:<10} {:>10} {:>7.1}% {:>12?} {:>12?}",
+                format.to_string(),
+                compression.to_string(),
+                encoded.len(),
+                encoded.len() as f64 / plain_len as f64 * 100.0,
+                save,
+                load
+            );
+        }
+    }
+}
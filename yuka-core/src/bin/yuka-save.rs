@@ -0,0 +1,253 @@
+//!
+//! セーブファイルをゲーム外で調査・編集するためのツール
+//!
+//! yuka-save decrypt <save> [--json]       復号して現在のスキーマで出力する
+//! yuka-save encrypt <input> <save> [--format toml|json|cbor]
+//!                                         TOML/JSONを暗号化してセーブファイルにする
+//! yuka-save validate <save>               復号・マイグレーションできるか確認する
+//! yuka-save diff <save1> <save2>          二つのセーブデータの差分を表示する
+//! yuka-save export <save> [--compress]    共有用のテキストコードを出力する
+//! yuka-save import <code.txt> <save>      テキストコードをセーブファイルにする
+//!
+//! Godotに依存しないので、yuka-coreだけでビルドできる
+//! 鍵の素はゲームと同じく、ビルド時に環境変数YUKA_SAVE_SECRETで渡す
+//!
+
+use std::{path::Path, process::exit};
+
+use yuka_core::{
+    crypt::SaveKeys,
+    item::with_item_catalog,
+    save::{
+        codec::{self, Decoded, EncodeOptions, LegacyDecrypt, SaveFileFormat},
+        compression::Compression,
+        error::SaveError,
+        format::SaveFormat,
+        header,
+        migration::{read_version, CURRENT_SAVE_DATA_VERSION, LEGACY_SAVE_DATA_VERSION},
+        share,
+        statistics::format_playtime,
+        NativeSaveData,
+    },
+};
+
+const USAGE: &str = "usage:
+    yuka-save decrypt <save> [--json]
+    yuka-save encrypt <input.toml|input.json> <save> [--format toml|json|cbor]
+    yuka-save validate <save>
+    yuka-save diff <save1> <save2>
+    yuka-save export <save> [--compress]
+    yuka-save import <code.txt> <save>";
+
+///
+/// 認証導入前の古いフォーマットの復号はゲーム側にしか無いので、このツールでは読めない
+///
+fn legacy_unsupported(_: &[u8]) -> Result<String, String> {
+    Err("legacy saves can only be read by the game; load and save it once in the game".to_string())
+}
+
+///
+/// セーブファイルを読み書きするための鍵と、古いフォーマットの復号
+///
+struct Context {
+    keys: SaveKeys,
+    legacy: LegacyDecrypt,
+}
+
+impl Context {
+    fn read_save(&self, path: &str) -> Result<Decoded, String> {
+        let buf = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        codec::decode(&self.keys, &buf, self.legacy).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn to_toml_value(save_data: &mut NativeSaveData) -> Result<toml::Value, String> {
+    let s = save_data.to_toml_string().map_err(|e| e.to_string())?;
+    toml::from_str(&s).map_err(|e| e.to_string())
+}
+
+fn decrypt(ctx: &Context, path: &str, json: bool) -> Result<(), String> {
+    let mut decoded = ctx.read_save(path)?;
+
+    if json {
+        let value = to_toml_value(&mut decoded.data)?;
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?
+        );
+    } else {
+        print!(
+            "{}",
+            decoded.data.to_toml_string().map_err(|e| e.to_string())?
+        );
+    }
+
+    Ok(())
+}
+
+fn encrypt(ctx: &Context, input: &str, output: &str, format: SaveFormat) -> Result<(), String> {
+    let content = std::fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?;
+
+    let value = if Path::new(input)
+        .extension()
+        .is_some_and(|ext| ext == "json")
+    {
+        serde_json::from_str::<toml::Value>(&content).map_err(|e| SaveError::Parse(e.to_string()))
+    } else {
+        toml::from_str::<toml::Value>(&content).map_err(|e| SaveError::Parse(e.to_string()))
+    };
+
+    let mut save_data = value
+        .and_then(NativeSaveData::from_value)
+        .map_err(|e| format!("{}: {}", input, e))?;
+
+    let options = EncodeOptions {
+        format,
+        ..EncodeOptions::default()
+    };
+    let buf = codec::encode_with(&ctx.keys, &mut save_data, options).map_err(|e| e.to_string())?;
+    std::fs::write(output, buf).map_err(|e| format!("{}: {}", output, e))
+}
+
+fn export(ctx: &Context, path: &str, compression: Compression) -> Result<(), String> {
+    let mut decoded = ctx.read_save(path)?;
+    let code =
+        share::export(&ctx.keys, &mut decoded.data, compression).map_err(|e| e.to_string())?;
+    println!("{}", code);
+
+    Ok(())
+}
+
+fn import(ctx: &Context, input: &str, output: &str) -> Result<(), String> {
+    let code = std::fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?;
+    let mut save_data = share::import(&ctx.keys, &code).map_err(|e| format!("{}: {}", input, e))?;
+
+    let buf = codec::encode(&ctx.keys, &mut save_data).map_err(|e| e.to_string())?;
+    std::fs::write(output, buf).map_err(|e| format!("{}: {}", output, e))
+}
+
+fn validate(ctx: &Context, path: &str) -> Result<(), String> {
+    let buf = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let (value, format) =
+        codec::decrypt(&ctx.keys, &buf, ctx.legacy).map_err(|e| format!("{}: {}", path, e))?;
+
+    let version = value
+        .as_table()
+        .map_or(Ok(LEGACY_SAVE_DATA_VERSION), read_version)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let save_data = NativeSaveData::from_value(value).map_err(|e| format!("{}: {}", path, e))?;
+
+    println!(
+        "{}: ok (format: {}, schema version: {} -> {})",
+        path,
+        match format {
+            SaveFileFormat::Legacy => "legacy",
+            SaveFileFormat::Sealed => "sealed",
+        },
+        version,
+        CURRENT_SAVE_DATA_VERSION
+    );
+    if let Ok(Some(header)) = header::read_slot_header(Path::new(path)) {
+        println!(
+            "  body: {}, compression: {}",
+            header.format, header.compression
+        );
+    }
+    println!("  date: {}", save_data.get_date());
+    println!("  real date: {}", save_data.get_real_date());
+    println!("  item kinds: {}", save_data.get_items().size());
+    with_item_catalog(|catalog| {
+        for (id, count) in save_data.get_items().iter() {
+            if !catalog.contains(id) {
+                println!("    unknown item: {} x{}", id, count);
+            }
+        }
+    });
+    println!(
+        "  playtime: {} ({} sessions)",
+        format_playtime(save_data.get_statistics().playtime_secs),
+        save_data.get_statistics().session_count
+    );
+    println!("  scene: {}", save_data.get_scene().path);
+
+    Ok(())
+}
+
+fn diff_value(path: &str, a: Option<&toml::Value>, b: Option<&toml::Value>, count: &mut usize) {
+    match (a, b) {
+        (Some(toml::Value::Table(a)), Some(toml::Value::Table(b))) => {
+            let mut keys = a.keys().chain(b.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_value(&child, a.get(key), b.get(key), count);
+            }
+        }
+        (a, b) if a == b => (),
+        (a, b) => {
+            *count += 1;
+            let show = |v: Option<&toml::Value>| v.map_or("(none)".to_string(), |v| v.to_string());
+            println!("{}: {} -> {}", path, show(a), show(b));
+        }
+    }
+}
+
+fn diff(ctx: &Context, path1: &str, path2: &str) -> Result<(), String> {
+    let a = to_toml_value(&mut ctx.read_save(path1)?.data)?;
+    let b = to_toml_value(&mut ctx.read_save(path2)?.data)?;
+
+    let mut count = 0;
+    diff_value("", Some(&a), Some(&b), &mut count);
+
+    if count == 0 {
+        println!("no differences");
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    let ctx = match SaveKeys::from_build_env() {
+        Some(keys) => Context {
+            keys,
+            legacy: legacy_unsupported,
+        },
+        None => {
+            eprintln!("error: {}", SaveError::NoSaveKey);
+            exit(1);
+        }
+    };
+
+    let result = match args.as_slice() {
+        ["decrypt", path] => decrypt(&ctx, path, false),
+        ["decrypt", path, "--json"] => decrypt(&ctx, path, true),
+        ["encrypt", input, output] => encrypt(&ctx, input, output, SaveFormat::default()),
+        ["encrypt", input, output, "--format", format] => match format.parse() {
+            Ok(format) => encrypt(&ctx, input, output, format),
+            Err(e) => Err(e),
+        },
+        ["validate", path] => validate(&ctx, path),
+        ["diff", path1, path2] => diff(&ctx, path1, path2),
+        ["export", path] => export(&ctx, path, Compression::None),
+        ["export", path, "--compress"] => export(&ctx, path, Compression::Deflate),
+        ["import", input, output] => import(&ctx, input, output),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
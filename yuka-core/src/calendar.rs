@@ -0,0 +1,533 @@
+pub mod event;
+pub mod time;
+pub mod week;
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    convert::TryFrom,
+    fmt::Display,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+pub use time::{DayPhase, GensoTime, TimeAction};
+pub use week::{WeekAnchor, Weekday};
+
+pub const MONTHS_PER_SEASON: u8 = 12;
+
+/// 添字は月。0は使わない
+static DAYS_IN_MONTH: [u8; MONTHS_PER_SEASON as usize + 1] =
+    [0, 31, 28, 31, 30, 30, 30, 31, 31, 30, 31, 30, 31];
+
+pub const DAYS_PER_SEASON: i64 = 364;
+
+pub fn days_in_month(month: u8) -> Option<u8> {
+    if (1..=MONTHS_PER_SEASON).contains(&month) {
+        Some(DAYS_IN_MONTH[month as usize])
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidDate {
+    pub season: u32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Display for InvalidDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid date: {}季 {}月 {}日",
+            self.season, self.month, self.day
+        )
+    }
+}
+
+impl std::error::Error for InvalidDate {}
+
+///
+/// 日数を足した結果が0季より前、またはu32で表せない季になる
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateOutOfRange {
+    pub date: GensoDate,
+    pub days: i32,
+}
+
+impl Display for DateOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "date out of range: {} {:+} days", self.date, self.days)
+    }
+}
+
+impl std::error::Error for DateOutOfRange {}
+
+///
+/// フィールドの順序がそのまま日付の順序になる
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GensoDate {
+    pub season: u32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Display for GensoDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}季 {}月 {}日", self.season, self.month, self.day)
+    }
+}
+
+impl GensoDate {
+    pub const fn new(season: u32, month: u8, day: u8) -> Self {
+        GensoDate { season, month, day }
+    }
+
+    pub fn new_empty() -> Self {
+        GensoDate {
+            season: 0,
+            month: 0,
+            day: 0,
+        }
+    }
+
+    pub fn to_short_string(&self) -> String {
+        format!("{}月{}日", self.month, self.day)
+    }
+
+    pub fn to_month_string_eng_short(&self) -> String {
+        match self.month {
+            1 => "Jan.",
+            2 => "Feb.",
+            3 => "Mar.",
+            4 => "Apr.",
+            5 => "May",
+            6 => "Jun.",
+            7 => "Jul.",
+            8 => "Aug.",
+            9 => "Sep.",
+            10 => "Oct.",
+            11 => "Nov.",
+            12 => "Dec.",
+            _ => {
+                eprintln!("Invalid month");
+                "Dec."
+            }
+        }
+        .to_string()
+    }
+
+    pub fn add_day_chain(mut self, day: i32) -> Self {
+        self.add_day(day);
+        self
+    }
+
+    ///
+    /// dayは負の値でもよい。季をまたぐ場合も正しく計算する
+    /// 結果が表せる範囲を超える場合はpanicする。セーブデータの日付を進めるときはtry_add_dayを使う
+    ///
+    pub fn add_day(&mut self, day: i32) {
+        *self = self.checked_add_day(day).expect("GensoDate out of range");
+    }
+
+    pub fn checked_add_day(&self, day: i32) -> Option<Self> {
+        let ordinal = self.ordinal() + day as i64;
+
+        if ordinal < 0 || ordinal / DAYS_PER_SEASON > u32::MAX as i64 {
+            None
+        } else {
+            Some(Self::from_ordinal(ordinal))
+        }
+    }
+
+    pub fn try_add_day(&self, day: i32) -> Result<Self, DateOutOfRange> {
+        self.checked_add_day(day).ok_or(DateOutOfRange {
+            date: *self,
+            days: day,
+        })
+    }
+
+    ///
+    /// self -> 7/1
+    /// date2 -> 7/8
+    /// return 7
+    ///
+    pub fn diff_day(&self, date2: &Self) -> i32 {
+        *date2 - *self
+    }
+
+    pub fn is_past(&self, date: &GensoDate) -> bool {
+        self > date
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match days_in_month(self.month) {
+            Some(days) => 1 <= self.day && self.day <= days,
+            None => false,
+        }
+    }
+
+    ///
+    /// [self, end) の日付を順に返す
+    ///
+    pub fn days_until(&self, end: GensoDate) -> Days {
+        Days {
+            next: self.ordinal(),
+            end: end.ordinal(),
+        }
+    }
+
+    ///
+    /// [self, end] の日付を順に返す
+    ///
+    pub fn days_through(&self, end: GensoDate) -> Days {
+        Days {
+            next: self.ordinal(),
+            end: end.ordinal() + 1,
+        }
+    }
+
+    ///
+    /// 0季1月1日を0とした通算日数
+    ///
+    fn ordinal(&self) -> i64 {
+        let days_before_month = DAYS_IN_MONTH
+            .iter()
+            .take(self.month as usize)
+            .map(|days| *days as i64)
+            .sum::<i64>();
+
+        self.season as i64 * DAYS_PER_SEASON + days_before_month + self.day as i64 - 1
+    }
+
+    fn from_ordinal(ordinal: i64) -> Self {
+        let season = ordinal / DAYS_PER_SEASON;
+        let mut rest = ordinal % DAYS_PER_SEASON;
+
+        let mut month = 1;
+        while rest >= DAYS_IN_MONTH[month as usize] as i64 {
+            rest -= DAYS_IN_MONTH[month as usize] as i64;
+            month += 1;
+        }
+
+        GensoDate::new(season as u32, month, rest as u8 + 1)
+    }
+
+    pub fn first_day(&self) -> bool {
+        self == &GensoDate::new(112, 7, 23)
+    }
+}
+
+impl TryFrom<(u32, u8, u8)> for GensoDate {
+    type Error = InvalidDate;
+
+    fn try_from((season, month, day): (u32, u8, u8)) -> Result<Self, Self::Error> {
+        let date = GensoDate::new(season, month, day);
+
+        if date.is_valid() {
+            Ok(date)
+        } else {
+            Err(InvalidDate { season, month, day })
+        }
+    }
+}
+
+impl Add<i32> for GensoDate {
+    type Output = GensoDate;
+
+    fn add(self, day: i32) -> Self::Output {
+        self.add_day_chain(day)
+    }
+}
+
+impl AddAssign<i32> for GensoDate {
+    fn add_assign(&mut self, day: i32) {
+        self.add_day(day);
+    }
+}
+
+impl Sub<i32> for GensoDate {
+    type Output = GensoDate;
+
+    fn sub(self, day: i32) -> Self::Output {
+        self.add_day_chain(-day)
+    }
+}
+
+impl SubAssign<i32> for GensoDate {
+    fn sub_assign(&mut self, day: i32) {
+        self.add_day(-day);
+    }
+}
+
+///
+/// 符号付きの日数を返す。 b + (a - b) == a
+/// 差がi32に収まらない (約590万季以上離れている) 場合はpanicする
+///
+impl Sub<GensoDate> for GensoDate {
+    type Output = i32;
+
+    fn sub(self, other: GensoDate) -> Self::Output {
+        i32::try_from(self.ordinal() - other.ordinal()).expect("GensoDate difference out of range")
+    }
+}
+
+pub struct Days {
+    next: i64,
+    end: i64,
+}
+
+impl Iterator for Days {
+    type Item = GensoDate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < self.end {
+            let date = GensoDate::from_ordinal(self.next);
+            self.next += 1;
+            Some(date)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.next).max(0) as usize;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Days {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next < self.end {
+            self.end -= 1;
+            Some(GensoDate::from_ordinal(self.end))
+        } else {
+            None
+        }
+    }
+}
+
+impl ExactSizeIterator for Days {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn valid_date() -> impl Strategy<Value = GensoDate> {
+        (0u32..1000, 1u8..=MONTHS_PER_SEASON)
+            .prop_flat_map(|(season, month)| {
+                (Just(season), Just(month), 1..=days_in_month(month).unwrap())
+            })
+            .prop_map(|(season, month, day)| GensoDate::new(season, month, day))
+    }
+
+    proptest! {
+        #[test]
+        fn adding_difference_gives_other_date(a in valid_date(), b in valid_date()) {
+            prop_assert_eq!(a + (b - a), b);
+            prop_assert_eq!(b - (b - a), a);
+        }
+
+        #[test]
+        fn add_day_keeps_date_valid(a in valid_date(), days in -300_000i32..300_000) {
+            if let Some(date) = a.checked_add_day(days) {
+                prop_assert!(date.is_valid());
+                prop_assert_eq!(date - a, days);
+            }
+        }
+
+        #[test]
+        fn ordering_matches_difference(a in valid_date(), b in valid_date()) {
+            prop_assert_eq!(a.cmp(&b), 0.cmp(&(b - a)));
+        }
+
+        #[test]
+        fn days_through_has_one_more_day_than_days_until(a in valid_date(), len in 0i32..800) {
+            let b = a + len;
+            prop_assert_eq!(a.days_until(b).len(), len as usize);
+            prop_assert_eq!(a.days_through(b).len(), len as usize + 1);
+        }
+    }
+
+    #[test]
+    fn try_from_accepts_valid_dates() {
+        assert_eq!(
+            GensoDate::try_from((112, 2, 28)),
+            Ok(GensoDate::new(112, 2, 28))
+        );
+        assert_eq!(
+            GensoDate::try_from((112, 12, 31)),
+            Ok(GensoDate::new(112, 12, 31))
+        );
+    }
+
+    #[test]
+    fn try_from_rejects_invalid_dates() {
+        for (season, month, day) in [
+            (112, 13, 1),
+            (112, 2, 30),
+            (112, 2, 29),
+            (112, 0, 1),
+            (112, 1, 0),
+        ] {
+            assert_eq!(
+                GensoDate::try_from((season, month, day)),
+                Err(InvalidDate { season, month, day })
+            );
+        }
+        assert_eq!(
+            InvalidDate {
+                season: 112,
+                month: 13,
+                day: 1
+            }
+            .to_string(),
+            "invalid date: 112季 13月 1日"
+        );
+    }
+
+    #[test]
+    fn days_until_excludes_end() {
+        let days = GensoDate::new(112, 12, 30)
+            .days_until(GensoDate::new(113, 1, 2))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            days,
+            [
+                GensoDate::new(112, 12, 30),
+                GensoDate::new(112, 12, 31),
+                GensoDate::new(113, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn days_through_includes_end() {
+        let days = GensoDate::new(112, 2, 27)
+            .days_through(GensoDate::new(112, 3, 1))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            days,
+            [
+                GensoDate::new(112, 2, 27),
+                GensoDate::new(112, 2, 28),
+                GensoDate::new(112, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_and_reversed_ranges_have_no_days() {
+        let date = GensoDate::new(112, 5, 1);
+
+        assert_eq!(date.days_until(date).count(), 0);
+        assert_eq!(date.days_through(date).collect::<Vec<_>>(), [date]);
+        assert_eq!(date.days_until(date - 3).len(), 0);
+        assert_eq!(date.days_through(date - 1).count(), 0);
+    }
+
+    #[test]
+    fn days_can_be_iterated_backwards() {
+        let days = GensoDate::new(112, 5, 1)
+            .days_through(GensoDate::new(112, 5, 3))
+            .rev()
+            .map(|date| date.day)
+            .collect::<Vec<_>>();
+
+        assert_eq!(days, [3, 2, 1]);
+    }
+
+    #[test]
+    fn dates_are_ordered_by_season_month_day() {
+        assert!(GensoDate::new(112, 12, 31) < GensoDate::new(113, 1, 1));
+        assert!(GensoDate::new(112, 5, 31) < GensoDate::new(112, 6, 1));
+        assert!(GensoDate::new(112, 5, 1) < GensoDate::new(112, 5, 2));
+        assert!(GensoDate::new(112, 5, 2).is_past(&GensoDate::new(112, 5, 1)));
+    }
+
+    #[test]
+    fn add_day_rolls_over_month_and_season() {
+        assert_eq!(GensoDate::new(112, 1, 31) + 1, GensoDate::new(112, 2, 1));
+        assert_eq!(GensoDate::new(112, 2, 28) + 1, GensoDate::new(112, 3, 1));
+        assert_eq!(GensoDate::new(112, 12, 31) + 1, GensoDate::new(113, 1, 1));
+        assert_eq!(GensoDate::new(113, 1, 1) - 1, GensoDate::new(112, 12, 31));
+        assert_eq!(
+            GensoDate::new(112, 5, 1) + DAYS_PER_SEASON as i32,
+            GensoDate::new(113, 5, 1)
+        );
+    }
+
+    #[test]
+    fn add_assign_and_sub_assign() {
+        let mut date = GensoDate::new(112, 5, 1);
+        date += 40;
+        assert_eq!(date, GensoDate::new(112, 6, 11));
+        date -= 40;
+        assert_eq!(date, GensoDate::new(112, 5, 1));
+    }
+
+    #[test]
+    fn checked_add_day_rejects_dates_before_season_zero() {
+        assert_eq!(GensoDate::new(0, 1, 1).checked_add_day(-1), None);
+        assert_eq!(
+            GensoDate::new(0, 1, 2).checked_add_day(-1),
+            Some(GensoDate::new(0, 1, 1))
+        );
+    }
+
+    #[test]
+    fn checked_add_day_rejects_seasons_beyond_u32() {
+        let last = GensoDate::new(u32::MAX, 12, 31);
+        assert_eq!(last.checked_add_day(1), None);
+        assert_eq!(
+            last.checked_add_day(-1),
+            Some(GensoDate::new(u32::MAX, 12, 30))
+        );
+        assert_eq!(
+            last.try_add_day(1),
+            Err(DateOutOfRange {
+                date: last,
+                days: 1
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "GensoDate out of range")]
+    fn add_day_panics_before_season_zero() {
+        let _ = GensoDate::new(0, 1, 1) + -1;
+    }
+
+    #[test]
+    #[should_panic(expected = "GensoDate difference out of range")]
+    fn difference_beyond_i32_panics() {
+        let _ = GensoDate::new(u32::MAX, 1, 1) - GensoDate::new(0, 1, 1);
+    }
+
+    #[test]
+    fn diff_day_is_signed() {
+        let a = GensoDate::new(112, 7, 1);
+        let b = GensoDate::new(112, 7, 8);
+        assert_eq!(a.diff_day(&b), 7);
+        assert_eq!(b.diff_day(&a), -7);
+    }
+
+    #[test]
+    fn days_in_month_rejects_invalid_months() {
+        assert_eq!(days_in_month(0), None);
+        assert_eq!(days_in_month(2), Some(28));
+        assert_eq!(days_in_month(13), None);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(GensoDate::new(112, 5, 1).to_string(), "112季 5月 1日");
+        assert_eq!(GensoDate::new(112, 5, 1).to_short_string(), "5月1日");
+    }
+}
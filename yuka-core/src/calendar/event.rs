@@ -0,0 +1,342 @@
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::{GensoDate, Weekday};
+
+///
+/// イベントがいつ起きるか
+///
+/// ```toml
+/// [[event]]
+/// id = "summer_festival"
+/// name = "夏祭り"
+/// schedule = { kind = "yearly", month = 8, day = 15 }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventSchedule {
+    Once {
+        date: GensoDate,
+    },
+    /// 毎季の同じ月日
+    Yearly {
+        month: u8,
+        day: u8,
+    },
+    /// 毎月の同じ日
+    Monthly {
+        day: u8,
+    },
+    Weekly {
+        weekday: Weekday,
+    },
+    /// startからinterval日ごと
+    Interval {
+        start: GensoDate,
+        interval: u32,
+    },
+    /// fromからtoまで毎日 (両端を含む)
+    Range {
+        from: GensoDate,
+        to: GensoDate,
+    },
+}
+
+impl EventSchedule {
+    pub fn occurs_on(&self, date: &GensoDate) -> bool {
+        match self {
+            Self::Once { date: target } => target == date,
+            Self::Yearly { month, day } => date.month == *month && date.day == *day,
+            Self::Monthly { day } => date.day == *day,
+            Self::Weekly { weekday } => date.weekday() == *weekday,
+            Self::Interval { start, interval } => {
+                *interval > 0 && start <= date && (*date - *start) % *interval as i32 == 0
+            }
+            Self::Range { from, to } => from <= date && date <= to,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarEvent {
+    pub id: String,
+    pub name: String,
+    pub schedule: EventSchedule,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventScheduler {
+    #[serde(default, rename = "event")]
+    events: Vec<CalendarEvent>,
+}
+
+impl EventScheduler {
+    pub fn new() -> Self {
+        EventScheduler { events: Vec::new() }
+    }
+
+    pub fn from_toml_str(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    pub fn add_event(&mut self, event: CalendarEvent) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[CalendarEvent] {
+        &self.events
+    }
+
+    pub fn events_on(&self, date: &GensoDate) -> Vec<&CalendarEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.schedule.occurs_on(date))
+            .collect()
+    }
+
+    ///
+    /// 日付がfromからtoに進んだときに起きるイベントを日付順に返す
+    /// fromの日のイベントは既に起きているものとして含めない
+    ///
+    pub fn events_between(
+        &self,
+        from: &GensoDate,
+        to: &GensoDate,
+    ) -> Vec<(GensoDate, &CalendarEvent)> {
+        if to <= from {
+            return Vec::new();
+        }
+
+        (*from + 1)
+            .days_through(*to)
+            .flat_map(|date| {
+                self.events_on(&date)
+                    .into_iter()
+                    .map(move |event| (date, event))
+            })
+            .collect()
+    }
+}
+
+///
+/// events.tomlから読み込んだイベント。日次処理とEventSchedulerノードで共有する
+///
+static EVENT_SCHEDULER: RwLock<Option<Arc<EventScheduler>>> = RwLock::new(None);
+
+pub fn set_event_scheduler(scheduler: Arc<EventScheduler>) {
+    *EVENT_SCHEDULER.write().unwrap() = Some(scheduler);
+}
+
+///
+/// まだ読み込んでいない場合は、イベントの無いスケジューラを返す
+///
+pub fn event_scheduler() -> Arc<EventScheduler> {
+    EVENT_SCHEDULER.read().unwrap().clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = r#"
+[[event]]
+id = "new_year"
+name = "元日"
+schedule = { kind = "yearly", month = 1, day = 1 }
+
+[[event]]
+id = "new_year_eve"
+name = "大晦日"
+schedule = { kind = "yearly", month = 12, day = 31 }
+
+[[event]]
+id = "rent"
+name = "家賃"
+schedule = { kind = "monthly", day = 1 }
+
+[[event]]
+id = "opening"
+name = "開店"
+schedule = { kind = "once", date = { season = 113, month = 1, day = 2 } }
+"#;
+
+    fn event(id: &str, schedule: EventSchedule) -> CalendarEvent {
+        CalendarEvent {
+            id: id.to_string(),
+            name: id.to_string(),
+            schedule,
+        }
+    }
+
+    fn fired_between(scheduler: &EventScheduler, from: GensoDate, to: GensoDate) -> Vec<String> {
+        scheduler
+            .events_between(&from, &to)
+            .into_iter()
+            .map(|(date, event)| {
+                format!("{}/{}/{} {}", date.season, date.month, date.day, event.id)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn events_between_crosses_season_boundary() {
+        let scheduler = EventScheduler::from_toml_str(EVENTS).unwrap();
+
+        assert_eq!(
+            fired_between(
+                &scheduler,
+                GensoDate::new(112, 12, 30),
+                GensoDate::new(113, 1, 2)
+            ),
+            [
+                "112/12/31 new_year_eve",
+                "113/1/1 new_year",
+                "113/1/1 rent",
+                "113/1/2 opening",
+            ]
+        );
+    }
+
+    #[test]
+    fn events_between_excludes_start_and_empty_ranges() {
+        let scheduler = EventScheduler::from_toml_str(EVENTS).unwrap();
+        let new_year = GensoDate::new(113, 1, 1);
+
+        assert_eq!(
+            fired_between(&scheduler, new_year, GensoDate::new(113, 1, 2)),
+            ["113/1/2 opening"]
+        );
+        assert!(scheduler.events_between(&new_year, &new_year).is_empty());
+        assert!(scheduler
+            .events_between(&new_year, &GensoDate::new(112, 12, 1))
+            .is_empty());
+    }
+
+    #[test]
+    fn weekly_event_occurs_every_seven_days() {
+        // 既定の基準では112季7月23日が日曜日
+        let schedule = EventSchedule::Weekly {
+            weekday: Weekday::Sunday,
+        };
+
+        assert!(schedule.occurs_on(&GensoDate::new(112, 7, 23)));
+        assert!(!schedule.occurs_on(&GensoDate::new(112, 7, 24)));
+        assert!(schedule.occurs_on(&GensoDate::new(112, 7, 30)));
+        assert!(schedule.occurs_on(&GensoDate::new(112, 7, 16)));
+
+        let mut scheduler = EventScheduler::new();
+        scheduler.add_event(event("market", schedule));
+        assert_eq!(
+            fired_between(
+                &scheduler,
+                GensoDate::new(112, 7, 20),
+                GensoDate::new(112, 8, 6)
+            ),
+            ["112/7/23 market", "112/7/30 market", "112/8/6 market"]
+        );
+    }
+
+    #[test]
+    fn interval_event_starts_at_start_date() {
+        let start = GensoDate::new(112, 12, 30);
+        let schedule = EventSchedule::Interval { start, interval: 3 };
+
+        assert!(!schedule.occurs_on(&(start - 3)));
+        assert!(schedule.occurs_on(&start));
+        assert!(!schedule.occurs_on(&(start + 1)));
+        assert!(schedule.occurs_on(&GensoDate::new(113, 1, 2)));
+        assert!(schedule.occurs_on(&GensoDate::new(113, 1, 5)));
+    }
+
+    #[test]
+    fn interval_zero_never_occurs() {
+        let start = GensoDate::new(112, 5, 1);
+        let schedule = EventSchedule::Interval { start, interval: 0 };
+
+        assert!(!schedule.occurs_on(&start));
+    }
+
+    #[test]
+    fn range_event_includes_both_ends() {
+        let schedule = EventSchedule::Range {
+            from: GensoDate::new(112, 12, 31),
+            to: GensoDate::new(113, 1, 2),
+        };
+
+        assert!(!schedule.occurs_on(&GensoDate::new(112, 12, 30)));
+        assert!(schedule.occurs_on(&GensoDate::new(112, 12, 31)));
+        assert!(schedule.occurs_on(&GensoDate::new(113, 1, 1)));
+        assert!(schedule.occurs_on(&GensoDate::new(113, 1, 2)));
+        assert!(!schedule.occurs_on(&GensoDate::new(113, 1, 3)));
+
+        let mut scheduler = EventScheduler::new();
+        scheduler.add_event(event("festival", schedule));
+        assert_eq!(
+            scheduler
+                .events_between(&GensoDate::new(112, 12, 1), &GensoDate::new(113, 2, 1))
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn schedules_are_read_from_toml() {
+        let scheduler = EventScheduler::from_toml_str(
+            r#"
+[[event]]
+id = "market"
+name = "市"
+schedule = { kind = "weekly", weekday = "Sunday" }
+
+[[event]]
+id = "watering"
+name = "水やり"
+schedule = { kind = "interval", start = { season = 112, month = 5, day = 1 }, interval = 2 }
+
+[[event]]
+id = "festival"
+name = "祭り"
+schedule = { kind = "range", from = { season = 112, month = 8, day = 13 }, to = { season = 112, month = 8, day = 15 } }
+"#,
+        )
+        .unwrap();
+
+        let schedules = scheduler
+            .events()
+            .iter()
+            .map(|event| event.schedule.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            schedules,
+            [
+                EventSchedule::Weekly {
+                    weekday: Weekday::Sunday
+                },
+                EventSchedule::Interval {
+                    start: GensoDate::new(112, 5, 1),
+                    interval: 2
+                },
+                EventSchedule::Range {
+                    from: GensoDate::new(112, 8, 13),
+                    to: GensoDate::new(112, 8, 15)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn loaded_scheduler_is_shared() {
+        let scheduler = Arc::new(EventScheduler::from_toml_str(EVENTS).unwrap());
+        set_event_scheduler(scheduler.clone());
+
+        assert!(Arc::ptr_eq(&event_scheduler(), &scheduler));
+        assert_eq!(
+            event_scheduler()
+                .events_on(&GensoDate::new(113, 1, 1))
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+
+use std::{fmt::Display, str::FromStr};
+
+pub const MINUTES_PER_HOUR: u32 = 60;
+pub const MINUTES_PER_DAY: u32 = 24 * MINUTES_PER_HOUR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GensoTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl Display for GensoTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl GensoTime {
+    pub const fn new(hour: u8, minute: u8) -> Self {
+        GensoTime { hour, minute }
+    }
+
+    /// 新しい一日が始まる時刻
+    pub const fn morning() -> Self {
+        GensoTime::new(6, 0)
+    }
+
+    pub fn from_minutes(minutes: u32) -> Self {
+        let minutes = minutes % MINUTES_PER_DAY;
+        GensoTime::new(
+            (minutes / MINUTES_PER_HOUR) as u8,
+            (minutes % MINUTES_PER_HOUR) as u8,
+        )
+    }
+
+    pub fn minutes(&self) -> u32 {
+        self.hour as u32 * MINUTES_PER_HOUR + self.minute as u32
+    }
+
+    ///
+    /// 時間を進めて、日付が変わった回数を返す
+    ///
+    pub fn advance(&mut self, minutes: u32) -> u32 {
+        // 丸一日分を先に数えておき、u32::MAX分進めても溢れないようにする
+        let days = minutes / MINUTES_PER_DAY;
+        let total = self.minutes() + minutes % MINUTES_PER_DAY;
+        *self = Self::from_minutes(total);
+
+        days + total / MINUTES_PER_DAY
+    }
+
+    pub fn day_phase(&self) -> DayPhase {
+        DayPhase::ALL
+            .iter()
+            .rev()
+            .find(|phase| phase.start() <= *self)
+            .copied()
+            .unwrap_or(DayPhase::Night)
+    }
+}
+
+impl Default for GensoTime {
+    fn default() -> Self {
+        Self::morning()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DayPhase {
+    Dawn,
+    Morning,
+    Noon,
+    Evening,
+    Night,
+}
+
+impl DayPhase {
+    /// 開始時刻の順に並べる
+    pub const ALL: [DayPhase; 5] = [
+        DayPhase::Dawn,
+        DayPhase::Morning,
+        DayPhase::Noon,
+        DayPhase::Evening,
+        DayPhase::Night,
+    ];
+
+    pub fn start(&self) -> GensoTime {
+        match self {
+            Self::Dawn => GensoTime::new(4, 0),
+            Self::Morning => GensoTime::new(6, 0),
+            Self::Noon => GensoTime::new(11, 0),
+            Self::Evening => GensoTime::new(16, 0),
+            Self::Night => GensoTime::new(19, 0),
+        }
+    }
+
+    pub fn get_display_name(&self) -> &str {
+        match self {
+            Self::Dawn => "明け方",
+            Self::Morning => "朝",
+            Self::Noon => "昼",
+            Self::Evening => "夕方",
+            Self::Night => "夜",
+        }
+    }
+}
+
+impl Display for DayPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.get_display_name())
+    }
+}
+
+///
+/// 行動ごとにかかる時間
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeAction {
+    Water,
+    Fertilize,
+    Plant,
+    Harvest,
+    Talk,
+    Shopping,
+    Rest,
+}
+
+impl TimeAction {
+    pub fn minutes(&self) -> u32 {
+        match self {
+            Self::Water => 30,
+            Self::Fertilize => 30,
+            Self::Plant => 60,
+            Self::Harvest => 60,
+            Self::Talk => 20,
+            Self::Shopping => 60,
+            Self::Rest => 120,
+        }
+    }
+}
+
+impl FromStr for TimeAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Water" => Ok(Self::Water),
+            "Fertilize" => Ok(Self::Fertilize),
+            "Plant" => Ok(Self::Plant),
+            "Harvest" => Ok(Self::Harvest),
+            "Talk" => Ok(Self::Talk),
+            "Shopping" => Ok(Self::Shopping),
+            "Rest" => Ok(Self::Rest),
+            _ => Err(format!("unknown action: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_returns_rollover_count() {
+        let mut time = GensoTime::new(23, 30);
+        assert_eq!(time.advance(29), 0);
+        assert_eq!(time, GensoTime::new(23, 59));
+
+        assert_eq!(time.advance(1), 1);
+        assert_eq!(time, GensoTime::new(0, 0));
+
+        assert_eq!(time.advance(0), 0);
+        assert_eq!(time.advance(MINUTES_PER_DAY), 1);
+        assert_eq!(time, GensoTime::new(0, 0));
+
+        let mut time = GensoTime::new(22, 0);
+        assert_eq!(time.advance(2 * MINUTES_PER_DAY + 3 * MINUTES_PER_HOUR), 3);
+        assert_eq!(time, GensoTime::new(1, 0));
+    }
+
+    #[test]
+    fn advance_does_not_overflow() {
+        let mut time = GensoTime::new(23, 59);
+        let days = time.advance(u32::MAX);
+
+        let total = 23 * 60 + 59 + u32::MAX as u64;
+        assert_eq!(days as u64, total / MINUTES_PER_DAY as u64);
+        assert_eq!(
+            time,
+            GensoTime::from_minutes((total % MINUTES_PER_DAY as u64) as u32)
+        );
+    }
+
+    #[test]
+    fn day_phase_boundaries() {
+        let cases = [
+            ((0, 0), DayPhase::Night),
+            ((3, 59), DayPhase::Night),
+            ((4, 0), DayPhase::Dawn),
+            ((5, 59), DayPhase::Dawn),
+            ((6, 0), DayPhase::Morning),
+            ((10, 59), DayPhase::Morning),
+            ((11, 0), DayPhase::Noon),
+            ((15, 59), DayPhase::Noon),
+            ((16, 0), DayPhase::Evening),
+            ((18, 59), DayPhase::Evening),
+            ((19, 0), DayPhase::Night),
+            ((23, 59), DayPhase::Night),
+        ];
+
+        for ((hour, minute), phase) in cases {
+            assert_eq!(
+                GensoTime::new(hour, minute).day_phase(),
+                phase,
+                "{:02}:{:02}",
+                hour,
+                minute
+            );
+        }
+    }
+
+    #[test]
+    fn time_action_from_str() {
+        assert_eq!("Water".parse(), Ok(TimeAction::Water));
+        assert_eq!("Rest".parse::<TimeAction>().unwrap().minutes(), 120);
+        assert_eq!(
+            "water".parse::<TimeAction>(),
+            Err("unknown action: water".to_string())
+        );
+        assert!("".parse::<TimeAction>().is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+use std::{fmt::Display, sync::RwLock};
+
+use super::GensoDate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Sunday,
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: i64) -> Self {
+        Self::ALL[index.rem_euclid(7) as usize]
+    }
+
+    pub fn succ(self) -> Self {
+        Self::from_index(self.index() as i64 + 1)
+    }
+
+    ///
+    /// selfから数えてtargetが何日後か (0 ~ 6)
+    ///
+    pub fn days_until(self, target: Weekday) -> i32 {
+        (target.index() as i32 - self.index() as i32).rem_euclid(7)
+    }
+
+    pub fn get_display_name(&self) -> &str {
+        match self {
+            Self::Sunday => "日",
+            Self::Monday => "月",
+            Self::Tuesday => "火",
+            Self::Wednesday => "水",
+            Self::Thursday => "木",
+            Self::Friday => "金",
+            Self::Saturday => "土",
+        }
+    }
+}
+
+impl Display for Weekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.get_display_name())
+    }
+}
+
+///
+/// 曜日の基準。dateがweekdayであることを表す
+/// ゲームデータ (calendar.toml) の [week] から読み込む
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeekAnchor {
+    pub weekday: Weekday,
+    /// 週の始まりの曜日
+    pub first_weekday: Weekday,
+    pub date: GensoDate,
+}
+
+impl WeekAnchor {
+    pub const DEFAULT: WeekAnchor = WeekAnchor {
+        weekday: Weekday::Sunday,
+        first_weekday: Weekday::Sunday,
+        date: GensoDate::new(112, 7, 23),
+    };
+
+    pub fn from_toml_str(s: &str) -> Result<Self, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct CalendarData {
+            week: WeekAnchor,
+        }
+
+        toml::from_str::<CalendarData>(s).map(|data| data.week)
+    }
+}
+
+impl Default for WeekAnchor {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static WEEK_ANCHOR: RwLock<WeekAnchor> = RwLock::new(WeekAnchor::DEFAULT);
+
+pub fn set_week_anchor(anchor: WeekAnchor) {
+    *WEEK_ANCHOR.write().unwrap() = anchor;
+}
+
+pub fn week_anchor() -> WeekAnchor {
+    *WEEK_ANCHOR.read().unwrap()
+}
+
+impl GensoDate {
+    pub fn weekday(&self) -> Weekday {
+        self.weekday_with(&week_anchor())
+    }
+
+    pub fn weekday_with(&self, anchor: &WeekAnchor) -> Weekday {
+        Weekday::from_index(anchor.weekday.index() as i64 + (*self - anchor.date) as i64)
+    }
+
+    ///
+    /// 季の中で何週目か (1始まり)
+    /// 1月1日を含む週を1週目とする
+    ///
+    pub fn week_of_season(&self) -> u32 {
+        self.week_of_season_with(&week_anchor())
+    }
+
+    pub fn week_of_season_with(&self, anchor: &WeekAnchor) -> u32 {
+        let season_first = GensoDate::new(self.season, 1, 1);
+        let offset = anchor
+            .first_weekday
+            .days_until(season_first.weekday_with(anchor));
+
+        ((*self - season_first + offset) / 7) as u32 + 1
+    }
+
+    ///
+    /// selfより後で、最初にweekdayになる日付。selfがweekdayなら7日後
+    ///
+    pub fn next_weekday(&self, weekday: Weekday) -> GensoDate {
+        self.next_weekday_with(weekday, &week_anchor())
+    }
+
+    pub fn next_weekday_with(&self, weekday: Weekday, anchor: &WeekAnchor) -> GensoDate {
+        match self.weekday_with(anchor).days_until(weekday) {
+            0 => *self + 7,
+            days => *self + days,
+        }
+    }
+
+    pub fn is_week_first(&self) -> bool {
+        let anchor = week_anchor();
+        self.weekday_with(&anchor) == anchor.first_weekday
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANCHOR: WeekAnchor = WeekAnchor::DEFAULT;
+
+    fn monday_first() -> WeekAnchor {
+        WeekAnchor {
+            first_weekday: Weekday::Monday,
+            ..ANCHOR
+        }
+    }
+
+    #[test]
+    fn weekday_counts_from_anchor() {
+        let cases = [
+            (GensoDate::new(112, 7, 23), Weekday::Sunday),
+            (GensoDate::new(112, 7, 24), Weekday::Monday),
+            (GensoDate::new(112, 7, 29), Weekday::Saturday),
+            (GensoDate::new(112, 8, 1), Weekday::Tuesday),
+            // アンカーより前の日付
+            (GensoDate::new(112, 7, 22), Weekday::Saturday),
+            (GensoDate::new(112, 7, 16), Weekday::Sunday),
+            (GensoDate::new(112, 1, 1), Weekday::Monday),
+            // 一季は52週ちょうどなので、どの季も同じ月日は同じ曜日
+            (GensoDate::new(0, 1, 1), Weekday::Monday),
+            (GensoDate::new(113, 7, 23), Weekday::Sunday),
+        ];
+
+        for (date, weekday) in cases {
+            assert_eq!(date.weekday_with(&ANCHOR), weekday, "{}", date);
+        }
+    }
+
+    #[test]
+    fn weekday_uses_week_anchor() {
+        let date = GensoDate::new(112, 7, 24);
+        assert_eq!(date.weekday(), date.weekday_with(&week_anchor()));
+        assert_eq!(
+            date.next_weekday(Weekday::Friday),
+            date.next_weekday_with(Weekday::Friday, &week_anchor())
+        );
+    }
+
+    #[test]
+    fn week_of_season_starts_with_week_of_first_day() {
+        // 112季1月1日は月曜日
+        let cases = [
+            (GensoDate::new(112, 1, 1), 1, 1),
+            (GensoDate::new(112, 1, 6), 1, 1),
+            (GensoDate::new(112, 1, 7), 2, 1),
+            (GensoDate::new(112, 1, 8), 2, 2),
+            (GensoDate::new(112, 12, 31), 53, 52),
+        ];
+
+        for (date, sunday_first, monday_first_week) in cases {
+            assert_eq!(date.week_of_season_with(&ANCHOR), sunday_first, "{}", date);
+            assert_eq!(
+                date.week_of_season_with(&monday_first()),
+                monday_first_week,
+                "{}",
+                date
+            );
+        }
+    }
+
+    #[test]
+    fn next_weekday_is_after_self() {
+        let sunday = GensoDate::new(112, 7, 23);
+
+        assert_eq!(
+            sunday.next_weekday_with(Weekday::Monday, &ANCHOR),
+            GensoDate::new(112, 7, 24)
+        );
+        assert_eq!(
+            sunday.next_weekday_with(Weekday::Saturday, &ANCHOR),
+            GensoDate::new(112, 7, 29)
+        );
+        // 同じ曜日なら次の週
+        assert_eq!(
+            sunday.next_weekday_with(Weekday::Sunday, &ANCHOR),
+            GensoDate::new(112, 7, 30)
+        );
+        // 月と季をまたぐ
+        assert_eq!(
+            GensoDate::new(112, 12, 31).next_weekday_with(Weekday::Sunday, &ANCHOR),
+            GensoDate::new(113, 1, 7)
+        );
+    }
+
+    #[test]
+    fn days_until_wraps_around_week() {
+        assert_eq!(Weekday::Sunday.days_until(Weekday::Sunday), 0);
+        assert_eq!(Weekday::Saturday.days_until(Weekday::Sunday), 1);
+        assert_eq!(Weekday::Monday.days_until(Weekday::Sunday), 6);
+        assert_eq!(Weekday::Saturday.succ(), Weekday::Sunday);
+        assert_eq!(Weekday::from_index(-1), Weekday::Saturday);
+    }
+
+    #[test]
+    fn week_anchor_from_toml_str() {
+        let anchor = WeekAnchor::from_toml_str(
+            r#"
+[week]
+weekday = "Monday"
+first_weekday = "Monday"
+date = { season = 1, month = 1, day = 1 }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            anchor,
+            WeekAnchor {
+                weekday: Weekday::Monday,
+                first_weekday: Weekday::Monday,
+                date: GensoDate::new(1, 1, 1),
+            }
+        );
+        assert_eq!(
+            GensoDate::new(1, 1, 7).weekday_with(&anchor),
+            Weekday::Sunday
+        );
+    }
+
+    #[test]
+    fn week_anchor_rejects_invalid_toml() {
+        assert!(WeekAnchor::from_toml_str("").is_err());
+        assert!(WeekAnchor::from_toml_str(
+            r#"
+[week]
+weekday = "Funday"
+first_weekday = "Sunday"
+date = { season = 1, month = 1, day = 1 }
+"#
+        )
+        .is_err());
+        assert!(WeekAnchor::from_toml_str(
+            r#"
+[week]
+weekday = "Sunday"
+date = { season = 1, month = 1, day = 1 }
+"#
+        )
+        .is_err());
+    }
+}
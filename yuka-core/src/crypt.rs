@@ -0,0 +1,304 @@
+use crypto::{
+    aes::{self, KeySize},
+    hmac::Hmac,
+    mac::{Mac, MacResult},
+    sha2::Sha256,
+    symmetriccipher::SynchronousStreamCipher,
+};
+
+///
+/// 認証付きセーブファイルのフォーマット
+///
+/// v1: | magic (8) | format version (1) | iv (16) | ciphertext (AES-256-CTR) | tag (HMAC-SHA256, 32) |
+/// v2: | magic (8) | format version (1) | header len (4, LE) | header | iv (16) | ciphertext | tag (32) |
+///
+/// v2のheaderは平文で、本体を復号せずにスロット一覧を表示するために使う
+/// tagはmagicからciphertextまでのすべて(headerを含む)に対して計算する (encrypt-then-MAC)
+///
+pub const MAGIC: &[u8; 8] = b"YUKASAVE";
+pub const FORMAT_VERSION: u8 = 2;
+const FORMAT_VERSION_NO_HEADER: u8 = 1;
+
+const IV_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+const HEADER_LEN_SIZE: usize = 4;
+
+/// ヘッダ長を読むために必要な先頭のバイト数
+pub const PREFIX_LEN: usize = MAGIC.len() + 1 + HEADER_LEN_SIZE;
+
+const ENCRYPTION_KEY_LABEL: &str = "yuka-native/save-data/encryption";
+const MAC_KEY_LABEL: &str = "yuka-native/save-data/authentication";
+
+/// 鍵の素を渡す環境変数。ビルド時に設定する
+pub const SAVE_SECRET_ENV: &str = "YUKA_SAVE_SECRET";
+
+///
+/// セーブファイルの暗号化と改ざん検出に使う鍵
+/// 鍵の素になる秘密の値はソースに含めず、ビルド時に環境変数で受け取る
+///
+#[derive(Clone)]
+pub struct SaveKeys {
+    encryption: [u8; 32],
+    authentication: [u8; 32],
+}
+
+impl SaveKeys {
+    ///
+    /// 一つの秘密の値から、暗号化用と認証用に別々の鍵を作る
+    ///
+    pub fn derive(secret: &[u8]) -> Self {
+        SaveKeys {
+            encryption: derive_key(secret, ENCRYPTION_KEY_LABEL),
+            authentication: derive_key(secret, MAC_KEY_LABEL),
+        }
+    }
+
+    ///
+    /// ビルド時にYUKA_SAVE_SECRETで渡された鍵の素から作る。渡されずにビルドした場合はNone
+    ///
+    pub fn from_build_env() -> Option<Self> {
+        option_env!("YUKA_SAVE_SECRET").map(|secret| Self::derive(secret.as_bytes()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenError {
+    /// magicが無い。認証導入前の古いフォーマット
+    Legacy,
+    UnsupportedFormat(u8),
+    Truncated,
+    Tampered,
+    InvalidUtf8,
+}
+
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Legacy => write!(f, "unauthenticated legacy save format"),
+            Self::UnsupportedFormat(v) => write!(f, "unsupported save format version {}", v),
+            Self::Truncated => write!(f, "save file is truncated"),
+            Self::Tampered => write!(f, "save file is corrupted or has been tampered with"),
+            Self::InvalidUtf8 => write!(f, "decrypted save data is not valid UTF-8"),
+        }
+    }
+}
+
+fn derive_key(secret: &[u8], label: &str) -> [u8; 32] {
+    let mut hmac = Hmac::new(Sha256::new(), secret);
+    hmac.input(label.as_bytes());
+
+    let mut key = [0; 32];
+    key.copy_from_slice(hmac.result().code());
+    key
+}
+
+fn apply_keystream(keys: &SaveKeys, iv: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut cipher = aes::ctr(KeySize::KeySize256, &keys.encryption, iv);
+
+    let mut output = vec![0; input.len()];
+    cipher.process(input, &mut output);
+    output
+}
+
+fn compute_tag(keys: &SaveKeys, authenticated: &[u8]) -> MacResult {
+    let mut hmac = Hmac::new(Sha256::new(), &keys.authentication);
+    hmac.input(authenticated);
+    hmac.result()
+}
+
+/// 共有コードに付けるtagの長さ。コードを短くするため、HMAC-SHA256の先頭だけを使う
+pub const CHECKSUM_LEN: usize = 16;
+
+pub fn checksum(keys: &SaveKeys, data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0; CHECKSUM_LEN];
+    checksum.copy_from_slice(&compute_tag(keys, data).code()[..CHECKSUM_LEN]);
+    checksum
+}
+
+pub fn verify_checksum(keys: &SaveKeys, data: &[u8], checksum: &[u8]) -> bool {
+    // MacResultの比較は定数時間で行われる
+    MacResult::new(&compute_tag(keys, data).code()[..CHECKSUM_LEN]) == MacResult::new(checksum)
+}
+
+pub fn is_sealed(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+///
+/// ファイル先頭からヘッダ長を読み取る
+/// ヘッダを持たないv1ならNoneを返す
+///
+pub fn parse_prefix(prefix: &[u8]) -> Result<Option<usize>, OpenError> {
+    if !is_sealed(prefix) {
+        return Err(OpenError::Legacy);
+    }
+
+    match prefix.get(MAGIC.len()) {
+        Some(&FORMAT_VERSION) => (),
+        Some(&FORMAT_VERSION_NO_HEADER) => return Ok(None),
+        Some(&version) => return Err(OpenError::UnsupportedFormat(version)),
+        None => return Err(OpenError::Truncated),
+    }
+
+    let len_bytes = prefix
+        .get(MAGIC.len() + 1..PREFIX_LEN)
+        .ok_or(OpenError::Truncated)?;
+    let mut len = [0; HEADER_LEN_SIZE];
+    len.copy_from_slice(len_bytes);
+
+    Ok(Some(u32::from_le_bytes(len) as usize))
+}
+
+///
+/// bodyは圧縮済みのバイト列でもよい。どう解釈するかはheaderに記録しておく
+///
+pub fn seal(keys: &SaveKeys, header: &str, body: &[u8]) -> Vec<u8> {
+    let iv: [u8; IV_LEN] = rand::random();
+
+    let mut buf = Vec::with_capacity(PREFIX_LEN + header.len() + IV_LEN + body.len() + TAG_LEN);
+    buf.extend_from_slice(MAGIC);
+    buf.push(FORMAT_VERSION);
+    buf.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    buf.extend_from_slice(header.as_bytes());
+    buf.extend_from_slice(&iv);
+    buf.extend_from_slice(&apply_keystream(keys, &iv, body));
+
+    let tag = compute_tag(keys, &buf);
+    buf.extend_from_slice(tag.code());
+
+    buf
+}
+
+///
+/// headerとbodyを取り出す。v1にはheaderが無いのでNoneになる
+///
+pub fn open(keys: &SaveKeys, buf: &[u8]) -> Result<(Option<String>, Vec<u8>), OpenError> {
+    let header_len = parse_prefix(buf)?;
+
+    let header_range = header_len.map(|len| PREFIX_LEN..PREFIX_LEN + len);
+    let iv_start = match &header_range {
+        Some(range) => range.end,
+        None => MAGIC.len() + 1,
+    };
+
+    if buf.len() < iv_start + IV_LEN + TAG_LEN {
+        return Err(OpenError::Truncated);
+    }
+
+    let (authenticated, tag) = buf.split_at(buf.len() - TAG_LEN);
+    // MacResultの比較は定数時間で行われる
+    if compute_tag(keys, authenticated) != MacResult::new(tag) {
+        return Err(OpenError::Tampered);
+    }
+
+    let header = match header_range {
+        Some(range) => Some(
+            String::from_utf8(authenticated[range].to_vec()).map_err(|_| OpenError::InvalidUtf8)?,
+        ),
+        None => None,
+    };
+
+    let iv = &authenticated[iv_start..iv_start + IV_LEN];
+    let body = apply_keystream(keys, iv, &authenticated[iv_start + IV_LEN..]);
+
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> SaveKeys {
+        SaveKeys::derive(b"yuka-core/crypt/tests")
+    }
+
+    #[test]
+    fn sealed_buffer_opens_with_same_keys() {
+        let sealed = seal(&keys(), "version = 7", b"body");
+
+        assert!(is_sealed(&sealed));
+        assert_eq!(
+            open(&keys(), &sealed),
+            Ok((Some("version = 7".to_string()), b"body".to_vec()))
+        );
+    }
+
+    #[test]
+    fn body_is_encrypted() {
+        let sealed = seal(&keys(), "", b"plain text body");
+
+        assert!(!sealed
+            .windows(b"plain text body".len())
+            .any(|window| window == b"plain text body"));
+    }
+
+    #[test]
+    fn other_keys_are_rejected() {
+        let sealed = seal(&keys(), "", b"body");
+        let other = SaveKeys::derive(b"another secret");
+
+        assert_eq!(open(&other, &sealed), Err(OpenError::Tampered));
+    }
+
+    #[test]
+    fn modified_header_or_body_is_rejected() {
+        let sealed = seal(&keys(), "slot = 1", b"body");
+
+        let mut header_modified = sealed.clone();
+        header_modified[PREFIX_LEN] ^= 1;
+        assert_eq!(open(&keys(), &header_modified), Err(OpenError::Tampered));
+
+        let mut body_modified = sealed.clone();
+        let last_body_byte = body_modified.len() - TAG_LEN - 1;
+        body_modified[last_body_byte] ^= 1;
+        assert_eq!(open(&keys(), &body_modified), Err(OpenError::Tampered));
+    }
+
+    #[test]
+    fn truncated_buffer_is_rejected() {
+        let sealed = seal(&keys(), "", b"body");
+
+        assert_eq!(
+            open(&keys(), &sealed[..PREFIX_LEN]),
+            Err(OpenError::Truncated)
+        );
+        assert_eq!(
+            open(&keys(), &sealed[..MAGIC.len()]),
+            Err(OpenError::Truncated)
+        );
+    }
+
+    #[test]
+    fn unknown_buffers_are_legacy_or_unsupported() {
+        assert_eq!(open(&keys(), b"legacy"), Err(OpenError::Legacy));
+
+        let mut buf = MAGIC.to_vec();
+        buf.push(99);
+        assert_eq!(parse_prefix(&buf), Err(OpenError::UnsupportedFormat(99)));
+    }
+
+    #[test]
+    fn prefix_reports_header_length() {
+        let sealed = seal(&keys(), "12345", b"");
+
+        assert_eq!(parse_prefix(&sealed[..PREFIX_LEN]), Ok(Some(5)));
+
+        let mut v1 = MAGIC.to_vec();
+        v1.push(FORMAT_VERSION_NO_HEADER);
+        assert_eq!(parse_prefix(&v1), Ok(None));
+    }
+
+    #[test]
+    fn checksum_depends_on_data_and_keys() {
+        let checksum = checksum(&keys(), b"data");
+
+        assert!(verify_checksum(&keys(), b"data", &checksum));
+        assert!(!verify_checksum(&keys(), b"date", &checksum));
+        assert!(!verify_checksum(
+            &SaveKeys::derive(b"other"),
+            b"data",
+            &checksum
+        ));
+        assert!(!verify_checksum(&keys(), b"data", &checksum[1..]));
+    }
+}
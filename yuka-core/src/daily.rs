@@ -0,0 +1,360 @@
+use std::sync::Arc;
+
+use crate::{
+    calendar::{
+        event::{CalendarEvent, EventScheduler},
+        DateOutOfRange, GensoDate,
+    },
+    save::NativeSaveData,
+};
+
+///
+/// 日付が変わったときに実行される処理の順序
+/// 同じフェーズの中では登録順に実行する
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DailyPhase {
+    PlantGrowth,
+    Weather,
+    ShopRestock,
+    Events,
+}
+
+pub struct DayContext<'a> {
+    pub old: GensoDate,
+    pub new: GensoDate,
+    pub save_data: &'a mut NativeSaveData,
+    /// 起きたカレンダーイベント。セーブデータを離してから、日次処理の後にまとめて通知する
+    pub events: Vec<(GensoDate, CalendarEvent)>,
+}
+
+pub trait DailySystem {
+    fn name(&self) -> &str;
+    fn phase(&self) -> DailyPhase;
+    fn on_new_day(&mut self, ctx: &mut DayContext);
+}
+
+#[derive(Default)]
+pub struct DailySystems {
+    systems: Vec<Box<dyn DailySystem>>,
+}
+
+impl DailySystems {
+    pub fn new() -> Self {
+        DailySystems {
+            systems: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, system: Box<dyn DailySystem>) {
+        self.systems.push(system);
+        // 安定ソートなので同じフェーズ内の登録順は保たれる
+        self.systems.sort_by_key(|system| system.phase());
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.systems.iter().map(|system| system.name()).collect()
+    }
+
+    ///
+    /// 起きたカレンダーイベントを日付順に返す
+    ///
+    pub fn run(
+        &mut self,
+        save_data: &mut NativeSaveData,
+        old: GensoDate,
+        new: GensoDate,
+    ) -> Vec<(GensoDate, CalendarEvent)> {
+        let mut ctx = DayContext {
+            old,
+            new,
+            save_data,
+            events: Vec::new(),
+        };
+
+        for system in self.systems.iter_mut() {
+            system.on_new_day(&mut ctx);
+        }
+
+        ctx.events
+    }
+}
+
+///
+/// Eventsフェーズで、進んだ日に起きるカレンダーイベントを集める
+///
+pub struct CalendarEventSystem {
+    scheduler: Arc<EventScheduler>,
+}
+
+impl CalendarEventSystem {
+    pub fn new(scheduler: Arc<EventScheduler>) -> Self {
+        CalendarEventSystem { scheduler }
+    }
+}
+
+impl DailySystem for CalendarEventSystem {
+    fn name(&self) -> &str {
+        "calendar_events"
+    }
+
+    fn phase(&self) -> DailyPhase {
+        DailyPhase::Events
+    }
+
+    fn on_new_day(&mut self, ctx: &mut DayContext) {
+        let events = self.scheduler.events_between(&ctx.old, &ctx.new);
+        ctx.events.extend(
+            events
+                .into_iter()
+                .map(|(date, event)| (date, event.clone())),
+        );
+    }
+}
+
+pub struct DayAdvance {
+    pub old: GensoDate,
+    pub new: GensoDate,
+    pub events: Vec<(GensoDate, CalendarEvent)>,
+}
+
+///
+/// まだ日次処理をしていない日があれば、一日ずつ順に日次処理を実行する
+/// 時間を進めて日付が変わったときと、眠ったときに呼ぶ。処理する日が無ければNoneを返す
+///
+pub fn process_new_days(
+    save_data: &mut NativeSaveData,
+    systems: &mut DailySystems,
+) -> Option<DayAdvance> {
+    let (old, new) = save_data.take_unprocessed_days()?;
+
+    let mut events = Vec::new();
+    for date in old.days_until(new) {
+        events.extend(systems.run(save_data, date, date + 1));
+    }
+
+    Some(DayAdvance { old, new, events })
+}
+
+///
+/// 眠って次の日の朝にする。日付が進んだ場合は登録された日次処理を実行する
+/// 起きる前に時間を進めて日付が変わっていた場合も、その日の処理がまだなら実行する
+///
+pub fn sleep(
+    save_data: &mut NativeSaveData,
+    systems: &mut DailySystems,
+) -> Result<Option<DayAdvance>, DateOutOfRange> {
+    save_data.sleep_until_morning()?;
+    Ok(process_new_days(save_data, systems))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::{event::EventSchedule, GensoTime};
+
+    struct Recorder {
+        name: &'static str,
+        phase: DailyPhase,
+        log: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+    }
+
+    impl DailySystem for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn phase(&self) -> DailyPhase {
+            self.phase
+        }
+
+        fn on_new_day(&mut self, ctx: &mut DayContext) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}/{}", self.name, ctx.new.month, ctx.new.day));
+        }
+    }
+
+    #[test]
+    fn systems_run_in_phase_order() {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut systems = DailySystems::new();
+        for (name, phase) in [
+            ("restock", DailyPhase::ShopRestock),
+            ("growth", DailyPhase::PlantGrowth),
+            ("weather", DailyPhase::Weather),
+            ("growth2", DailyPhase::PlantGrowth),
+        ] {
+            systems.register(Box::new(Recorder {
+                name,
+                phase,
+                log: std::rc::Rc::clone(&log),
+            }));
+        }
+
+        assert_eq!(systems.names(), ["growth", "growth2", "weather", "restock"]);
+
+        let mut save_data = NativeSaveData::new();
+        systems.run(
+            &mut save_data,
+            GensoDate::new(112, 5, 1),
+            GensoDate::new(112, 5, 2),
+        );
+        assert_eq!(
+            *log.borrow(),
+            ["growth 5/2", "growth2 5/2", "weather 5/2", "restock 5/2"]
+        );
+    }
+
+    fn recording_systems() -> (DailySystems, std::rc::Rc<std::cell::RefCell<Vec<String>>>) {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut systems = DailySystems::new();
+        systems.register(Box::new(Recorder {
+            name: "growth",
+            phase: DailyPhase::PlantGrowth,
+            log: std::rc::Rc::clone(&log),
+        }));
+        (systems, log)
+    }
+
+    fn rent_scheduler() -> EventScheduler {
+        let mut scheduler = EventScheduler::new();
+        scheduler.add_event(CalendarEvent {
+            id: "rent".to_string(),
+            name: "家賃".to_string(),
+            schedule: EventSchedule::Monthly { day: 1 },
+        });
+        scheduler
+    }
+
+    #[test]
+    fn sleep_runs_systems_for_next_day() {
+        let (mut systems, log) = recording_systems();
+        let mut save_data = NativeSaveData::new();
+
+        let advance = sleep(&mut save_data, &mut systems).unwrap().unwrap();
+
+        assert_eq!(advance.old, GensoDate::new(112, 5, 1));
+        assert_eq!(advance.new, GensoDate::new(112, 5, 2));
+        assert_eq!(*log.borrow(), ["growth 5/2"]);
+    }
+
+    #[test]
+    fn sleep_after_midnight_runs_systems_skipped_by_advance_time() {
+        let (mut systems, log) = recording_systems();
+        let mut save_data = NativeSaveData::new();
+
+        // 6:00から20時間後は翌日の2:00
+        assert_eq!(save_data.advance_time(20 * 60), Ok(1));
+        assert_eq!(*save_data.get_date(), GensoDate::new(112, 5, 2));
+
+        let advance = sleep(&mut save_data, &mut systems).unwrap().unwrap();
+        assert_eq!(advance.old, GensoDate::new(112, 5, 1));
+        assert_eq!(advance.new, GensoDate::new(112, 5, 2));
+        assert_eq!(*save_data.get_time(), GensoTime::morning());
+        assert_eq!(*log.borrow(), ["growth 5/2"]);
+
+        assert!(process_new_days(&mut save_data, &mut systems).is_none());
+    }
+
+    #[test]
+    fn days_processed_after_advance_time_are_not_processed_again() {
+        let (mut systems, log) = recording_systems();
+        let mut save_data = NativeSaveData::new();
+
+        save_data.advance_time(20 * 60).unwrap();
+        assert!(process_new_days(&mut save_data, &mut systems).is_some());
+        assert!(sleep(&mut save_data, &mut systems).unwrap().is_none());
+
+        assert_eq!(*log.borrow(), ["growth 5/2"]);
+    }
+
+    #[test]
+    fn every_skipped_day_is_processed_in_order() {
+        let (mut systems, log) = recording_systems();
+        systems.register(Box::new(CalendarEventSystem::new(Arc::new(
+            rent_scheduler(),
+        ))));
+        let mut save_data = NativeSaveData::new();
+
+        save_data.advance_time(31 * 24 * 60).unwrap();
+        let advance = process_new_days(&mut save_data, &mut systems).unwrap();
+
+        assert_eq!(advance.old, GensoDate::new(112, 5, 1));
+        assert_eq!(advance.new, GensoDate::new(112, 6, 2));
+        assert_eq!(log.borrow().len(), 31);
+        assert_eq!(log.borrow().first().unwrap(), "growth 5/2");
+        assert_eq!(log.borrow().last().unwrap(), "growth 6/2");
+        assert_eq!(advance.events.len(), 1);
+        assert_eq!(advance.events[0].0, GensoDate::new(112, 6, 1));
+    }
+
+    #[test]
+    fn unprocessed_days_survive_save_and_load() {
+        let (mut systems, log) = recording_systems();
+        let mut save_data = NativeSaveData::new();
+        save_data.advance_time(20 * 60).unwrap();
+
+        let content = save_data.to_toml_string().unwrap();
+        let mut loaded = NativeSaveData::from_toml_str(&content).unwrap();
+
+        assert!(sleep(&mut loaded, &mut systems).unwrap().is_some());
+        assert_eq!(*log.borrow(), ["growth 5/2"]);
+        assert!(!loaded
+            .to_toml_string()
+            .unwrap()
+            .contains("unprocessed_since"));
+    }
+
+    #[test]
+    fn calendar_events_are_collected_in_events_phase() {
+        let mut scheduler = EventScheduler::new();
+        scheduler.add_event(CalendarEvent {
+            id: "rent".to_string(),
+            name: "家賃".to_string(),
+            schedule: EventSchedule::Monthly { day: 1 },
+        });
+
+        let mut systems = DailySystems::new();
+        systems.register(Box::new(CalendarEventSystem::new(Arc::new(scheduler))));
+
+        let mut save_data = NativeSaveData::new();
+        let events = systems.run(
+            &mut save_data,
+            GensoDate::new(112, 5, 30),
+            GensoDate::new(112, 6, 1),
+        );
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, GensoDate::new(112, 6, 1));
+        assert_eq!(events[0].1.id, "rent");
+    }
+
+    #[test]
+    fn date_out_of_range_changes_nothing() {
+        let (mut systems, log) = recording_systems();
+        let content = NativeSaveData::new().to_toml_string().unwrap().replace(
+            "season = 112\nmonth = 5\nday = 1",
+            "season = 4294967295\nmonth = 12\nday = 31",
+        );
+        let mut save_data = NativeSaveData::from_toml_str(&content).unwrap();
+        let last = GensoDate::new(u32::MAX, 12, 31);
+        assert_eq!(*save_data.get_date(), last);
+
+        let error = DateOutOfRange {
+            date: last,
+            days: 1,
+        };
+        assert_eq!(save_data.advance_time(20 * 60), Err(error.clone()));
+        assert_eq!(sleep(&mut save_data, &mut systems).err(), Some(error));
+
+        assert_eq!(*save_data.get_date(), last);
+        assert_eq!(*save_data.get_time(), GensoTime::morning());
+        assert_eq!(save_data.get_statistics().days_played, 0);
+        assert!(save_data.take_unprocessed_days().is_none());
+        assert!(log.borrow().is_empty());
+
+        // 日付が変わらなければ進められる
+        assert_eq!(save_data.advance_time(60), Ok(0));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::flag::{FlagCondition, StoryFlags};
+
+pub struct Serif {
+    speaker: String,
+    lines: Vec<String>,
+    current_line: usize,
+    condition: Option<FlagCondition>,
+    /// 会話ファイルの中で何番目のSerifか
+    index: usize,
+}
+
+impl Serif {
+    pub fn new(speaker: String, lines: Vec<String>) -> Self {
+        Serif {
+            speaker,
+            lines,
+            current_line: 0,
+            condition: None,
+            index: 0,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: FlagCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn is_available(&self, flags: &StoryFlags) -> bool {
+        self.condition
+            .as_ref()
+            .is_none_or(|condition| condition.evaluate(flags))
+    }
+
+    pub fn current_line_len(&self) -> usize {
+        let index = self.current_line % self.lines.len();
+        self.lines[index].len()
+    }
+
+    pub fn current_line(&self) -> &str {
+        let index = self.current_line % self.lines.len();
+        self.lines.get(index).unwrap()
+    }
+
+    pub fn next_line(&mut self) {
+        self.current_line += 1;
+    }
+
+    pub fn finish(&self) -> bool {
+        self.lines.len() <= self.current_line
+    }
+
+    pub fn get_speaker_name(&self) -> &str {
+        self.speaker.as_str()
+    }
+}
+
+pub struct Dialogue {
+    text: Vec<Serif>,
+    current_line: usize,
+}
+
+///
+/// 行頭に ?{条件} と書くと、条件を満たすときだけ表示する行になる
+/// 例: ?{npc.met_marisa} また会ったね
+/// 条件として読めない場合は、そのまま本文として表示する
+///
+fn parse_line(line: &str) -> Serif {
+    let speaker = "スピーカー".to_string();
+
+    if let Some(rest) = line.strip_prefix("?{") {
+        if let Some(end) = rest.find('}') {
+            if let Ok(condition) = rest[..end].parse::<FlagCondition>() {
+                let text = rest[end + 1..].trim_start().to_string();
+                return Serif::new(speaker, vec![text]).with_condition(condition);
+            }
+        }
+    }
+
+    Serif::new(speaker, vec![line.to_string()])
+}
+
+impl Dialogue {
+    pub fn parse(s: &str) -> Self {
+        let mut text = Vec::new();
+
+        for (index, line) in s.lines().enumerate() {
+            text.push(Serif {
+                index,
+                ..parse_line(line)
+            });
+        }
+
+        Dialogue {
+            text,
+            current_line: 0,
+        }
+    }
+
+    pub fn next_line(&mut self) {
+        self.current_line += 1;
+    }
+
+    ///
+    /// 何番目のSerifを表示しているか
+    /// retain_availableで取り除く前の位置なので、フラグが変わった後でも同じSerifを指す
+    ///
+    pub fn position(&self) -> usize {
+        self.get_current_serif()
+            .map_or(self.current_line, |serif| serif.index)
+    }
+
+    ///
+    /// セーブした位置から再開する
+    /// その位置のSerifが取り除かれている場合は次のSerif、範囲外の場合は最後のSerifにする
+    ///
+    pub fn seek(&mut self, position: usize) {
+        self.current_line = self
+            .text
+            .iter()
+            .position(|serif| serif.index >= position)
+            .unwrap_or(self.text.len().saturating_sub(1));
+    }
+
+    ///
+    /// 条件を満たさないSerifを取り除く。表示を始める前に呼ぶ
+    ///
+    pub fn retain_available(&mut self, flags: &StoryFlags) {
+        self.text.retain(|serif| serif.is_available(flags));
+    }
+
+    ///
+    /// 条件で全てのSerifが取り除かれた場合など、表示するものが無い
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    ///
+    /// 表示するSerifが無い場合はNone
+    ///
+    pub fn get_current_serif(&self) -> Option<&Serif> {
+        let index = self.current_line.checked_rem(self.text.len())?;
+        self.text.get(index)
+    }
+
+    pub fn get_current_serif_mut(&mut self) -> Option<&mut Serif> {
+        let index = self.current_line.checked_rem(self.text.len())?;
+        self.text.get_mut(index)
+    }
+
+    ///
+    /// 最後のSerifを表示している。空の場合も終わっているものとする
+    ///
+    pub fn finish(&self) -> bool {
+        self.text.len() <= self.current_line + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flag::FlagValue;
+
+    fn flags(entries: &[(&str, FlagValue)]) -> StoryFlags {
+        let mut flags = StoryFlags::new();
+        for (key, value) in entries {
+            flags.set(key, value.clone()).unwrap();
+        }
+        flags
+    }
+
+    fn current_line(dialogue: &Dialogue) -> String {
+        dialogue
+            .get_current_serif()
+            .unwrap()
+            .current_line()
+            .to_string()
+    }
+
+    fn lines(dialogue: &mut Dialogue) -> Vec<String> {
+        let mut lines = vec![current_line(dialogue)];
+        while !dialogue.finish() {
+            dialogue.next_line();
+            lines.push(current_line(dialogue));
+        }
+        lines
+    }
+
+    #[test]
+    fn every_line_is_a_serif() {
+        let mut dialogue = Dialogue::parse("こんにちは\nいい天気ですね");
+
+        assert_eq!(lines(&mut dialogue), ["こんにちは", "いい天気ですね"]);
+        assert_eq!(
+            dialogue.get_current_serif().unwrap().get_speaker_name(),
+            "スピーカー"
+        );
+    }
+
+    #[test]
+    fn conditional_lines_are_removed_when_condition_fails() {
+        let text =
+            "はじめまして\n?{npc.met_marisa} また会ったね\n?{shop.visits >= 3} いつもありがとう";
+
+        let mut dialogue = Dialogue::parse(text);
+        dialogue.retain_available(&flags(&[("shop.visits", FlagValue::Int(3))]));
+        assert_eq!(lines(&mut dialogue), ["はじめまして", "いつもありがとう"]);
+
+        let mut dialogue = Dialogue::parse(text);
+        dialogue.retain_available(&flags(&[("npc.met_marisa", FlagValue::Bool(true))]));
+        assert_eq!(lines(&mut dialogue), ["はじめまして", "また会ったね"]);
+    }
+
+    #[test]
+    fn unparsable_condition_is_shown_as_text() {
+        let mut dialogue = Dialogue::parse("?{not a condition 本文");
+        dialogue.retain_available(&StoryFlags::new());
+
+        assert_eq!(current_line(&dialogue), "?{not a condition 本文");
+    }
+
+    #[test]
+    fn seek_is_clamped_to_last_serif() {
+        let mut dialogue = Dialogue::parse("a\nb\nc");
+
+        dialogue.seek(1);
+        assert_eq!(dialogue.position(), 1);
+        assert_eq!(current_line(&dialogue), "b");
+
+        dialogue.seek(10);
+        assert_eq!(dialogue.position(), 2);
+        assert!(dialogue.finish());
+    }
+
+    #[test]
+    fn position_counts_removed_serifs() {
+        let text = "a\n?{npc.met} b\nc\nd";
+
+        let mut dialogue = Dialogue::parse(text);
+        dialogue.retain_available(&StoryFlags::new());
+        dialogue.next_line();
+        assert_eq!(current_line(&dialogue), "c");
+        assert_eq!(dialogue.position(), 2);
+
+        // フラグが変わって取り除かれる行が変わっても、同じSerifから再開する
+        let mut resumed = Dialogue::parse(text);
+        resumed.retain_available(&flags(&[("npc.met", FlagValue::Bool(true))]));
+        resumed.seek(2);
+        assert_eq!(current_line(&resumed), "c");
+        assert_eq!(resumed.position(), 2);
+    }
+
+    #[test]
+    fn seek_to_removed_serif_resumes_from_next_serif() {
+        let mut dialogue = Dialogue::parse("a\n?{npc.met} b\nc");
+        dialogue.retain_available(&StoryFlags::new());
+
+        dialogue.seek(1);
+        assert_eq!(current_line(&dialogue), "c");
+        assert_eq!(dialogue.position(), 2);
+    }
+
+    #[test]
+    fn dialogue_without_available_serif_is_finished() {
+        let mut dialogue = Dialogue::parse("?{npc.met} hi");
+        dialogue.retain_available(&StoryFlags::new());
+
+        assert!(dialogue.is_empty());
+        assert!(dialogue.finish());
+        assert!(dialogue.get_current_serif().is_none());
+        assert!(dialogue.get_current_serif_mut().is_none());
+
+        dialogue.seek(3);
+        assert_eq!(dialogue.position(), 0);
+        assert!(dialogue.get_current_serif().is_none());
+
+        let empty = Dialogue::parse("");
+        assert!(empty.is_empty());
+        assert!(empty.finish());
+    }
+
+    #[test]
+    fn serif_counts_its_lines() {
+        let mut serif = Serif::new(
+            "霊夢".to_string(),
+            vec!["一行目".to_string(), "二".to_string()],
+        );
+
+        assert_eq!(serif.current_line_len(), "一行目".len());
+        assert!(!serif.finish());
+        serif.next_line();
+        assert_eq!(serif.current_line(), "二");
+        serif.next_line();
+        assert!(serif.finish());
+    }
+}
@@ -0,0 +1,543 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{de, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::calendar::GensoDate;
+
+///
+/// フラグの値
+/// TOMLにそのまま書けるように、型名のタグは付けない
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FlagValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Date(GensoDate),
+}
+
+impl FlagValue {
+    ///
+    /// 条件で値そのものを書いたときの真偽
+    ///
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Bool(b) => *b,
+            Self::Int(n) => *n != 0,
+            Self::Str(s) => !s.is_empty(),
+            Self::Date(_) => true,
+        }
+    }
+
+    fn partial_cmp_same_kind(&self, other: &FlagValue) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a.partial_cmp(b),
+            (Self::Int(a), Self::Int(b)) => a.partial_cmp(b),
+            (Self::Str(a), Self::Str(b)) => a.partial_cmp(b),
+            (Self::Date(a), Self::Date(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FlagValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Str(s) => write!(f, "\"{}\"", s),
+            Self::Date(date) => write!(f, "{}/{}/{}", date.season, date.month, date.day),
+        }
+    }
+}
+
+///
+/// 条件式の右辺に書く値
+/// true/false、整数、季/月/日 の日付、"文字列"。それ以外はそのまま文字列として扱う
+///
+impl FromStr for FlagValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            return Err("empty flag value".to_string());
+        }
+
+        if let Ok(b) = s.parse::<bool>() {
+            return Ok(Self::Bool(b));
+        }
+
+        if let Ok(n) = s.parse::<i64>() {
+            return Ok(Self::Int(n));
+        }
+
+        if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+            return Ok(Self::Str(s[1..s.len() - 1].to_string()));
+        }
+
+        let parts = s.split('/').collect::<Vec<_>>();
+        if let [season, month, day] = parts.as_slice() {
+            if let (Ok(season), Ok(month), Ok(day)) = (
+                season.parse::<u32>(),
+                month.parse::<u8>(),
+                day.parse::<u8>(),
+            ) {
+                let date = GensoDate::new(season, month, day);
+                return if date.is_valid() {
+                    Ok(Self::Date(date))
+                } else {
+                    Err(format!("invalid date: {}", s))
+                };
+            }
+        }
+
+        Ok(Self::Str(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagError {
+    InvalidKey(String),
+}
+
+impl fmt::Display for FlagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidKey(key) => write!(
+                f,
+                "invalid flag key: {} (expected namespace.name, e.g. npc.met_marisa)",
+                key
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FlagError {}
+
+///
+/// フラグのキーは "名前空間.名前" の形にする (例: tutorial.finished, npc.met_marisa)
+/// 各部分は英数字と_だけを使う
+///
+pub fn is_valid_key(key: &str) -> bool {
+    let mut segments = 0;
+
+    for segment in key.split('.') {
+        if segment.is_empty()
+            || !segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return false;
+        }
+        segments += 1;
+    }
+
+    segments >= 2
+}
+
+///
+/// ストーリーの進行を記録するフラグと変数
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoryFlags {
+    flags: BTreeMap<String, FlagValue>,
+}
+
+///
+/// setと同じく、キーの形が正しくないフラグは読み込まない
+///
+impl<'de> Deserialize<'de> for StoryFlags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let flags = BTreeMap::<String, FlagValue>::deserialize(deserializer)?;
+
+        if let Some(key) = flags.keys().find(|key| !is_valid_key(key)) {
+            return Err(de::Error::custom(FlagError::InvalidKey(key.clone())));
+        }
+
+        Ok(StoryFlags { flags })
+    }
+}
+
+///
+/// TOMLではテーブルを値より後に書く必要があるので、テーブルになる日付のフラグを最後にする
+///
+impl Serialize for StoryFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (dates, values): (Vec<_>, Vec<_>) = self
+            .flags
+            .iter()
+            .partition(|(_, value)| matches!(value, FlagValue::Date(_)));
+
+        let mut map = serializer.serialize_map(Some(self.flags.len()))?;
+        for (key, value) in values.into_iter().chain(dates) {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl StoryFlags {
+    pub fn new() -> Self {
+        StoryFlags {
+            flags: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&FlagValue> {
+        self.flags.get(key)
+    }
+
+    /// 設定されていなければfalse
+    pub fn get_bool(&self, key: &str) -> bool {
+        self.get(key).is_some_and(FlagValue::is_truthy)
+    }
+
+    /// 設定されていないか整数でなければ0
+    pub fn get_int(&self, key: &str) -> i64 {
+        match self.get(key) {
+            Some(FlagValue::Int(n)) => *n,
+            _ => 0,
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.flags.contains_key(key)
+    }
+
+    ///
+    /// 値を設定し、以前の値を返す
+    ///
+    pub fn set(&mut self, key: &str, value: FlagValue) -> Result<Option<FlagValue>, FlagError> {
+        if !is_valid_key(key) {
+            return Err(FlagError::InvalidKey(key.to_string()));
+        }
+
+        Ok(self.flags.insert(key.to_string(), value))
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<FlagValue> {
+        self.flags.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FlagValue)> {
+        self.flags.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    ///
+    /// 名前空間に属するフラグをすべて返す
+    /// "npc" を渡すと "npc.met_marisa" や "npc.marisa.likes" が返る
+    ///
+    pub fn in_namespace<'a>(
+        &'a self,
+        namespace: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a FlagValue)> {
+        let prefix = format!("{}.", namespace);
+        self.flags
+            .range(prefix.clone()..)
+            .take_while(move |(key, _)| key.starts_with(&prefix))
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    /// 長い演算子から順に探す
+    const ALL: [(&'static str, CompareOp); 6] = [
+        ("==", Self::Eq),
+        ("!=", Self::Ne),
+        ("<=", Self::Le),
+        (">=", Self::Ge),
+        ("<", Self::Lt),
+        (">", Self::Gt),
+    ];
+}
+
+///
+/// フラグに対する条件式
+///
+/// key          設定されていて、値が真
+/// !key         上の否定
+/// key op value op は == != < <= > >= のどれか
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagCondition {
+    Truthy(String),
+    Falsy(String),
+    Compare(String, CompareOp, FlagValue),
+}
+
+impl FlagCondition {
+    pub fn evaluate(&self, flags: &StoryFlags) -> bool {
+        match self {
+            Self::Truthy(key) => flags.get_bool(key),
+            Self::Falsy(key) => !flags.get_bool(key),
+            Self::Compare(key, op, rhs) => {
+                let lhs = match flags.get(key) {
+                    Some(lhs) => lhs,
+                    // 設定されていないフラグはどの値とも等しくない
+                    None => return *op == CompareOp::Ne,
+                };
+
+                match op {
+                    CompareOp::Eq => lhs == rhs,
+                    CompareOp::Ne => lhs != rhs,
+                    op => match lhs.partial_cmp_same_kind(rhs) {
+                        Some(ordering) => match op {
+                            CompareOp::Lt => ordering.is_lt(),
+                            CompareOp::Le => ordering.is_le(),
+                            CompareOp::Gt => ordering.is_gt(),
+                            _ => ordering.is_ge(),
+                        },
+                        None => false,
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for FlagCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        // 一番左にある演算子を使う。同じ位置なら長い方 (<= と < なら <=)
+        let found = CompareOp::ALL
+            .iter()
+            .filter_map(|(token, op)| s.find(token).map(|index| (index, *token, *op)))
+            .min_by_key(|(index, _, _)| *index);
+
+        if let Some((index, token, op)) = found {
+            let key = s[..index].trim();
+            if !is_valid_key(key) {
+                return Err(FlagError::InvalidKey(key.to_string()).to_string());
+            }
+
+            let value = s[index + token.len()..].parse::<FlagValue>()?;
+            return Ok(Self::Compare(key.to_string(), op, value));
+        }
+
+        let (key, negate) = match s.strip_prefix('!') {
+            Some(key) => (key.trim(), true),
+            None => (s, false),
+        };
+
+        if !is_valid_key(key) {
+            return Err(FlagError::InvalidKey(key.to_string()).to_string());
+        }
+
+        if negate {
+            Ok(Self::Falsy(key.to_string()))
+        } else {
+            Ok(Self::Truthy(key.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::NativeSaveData;
+
+    #[test]
+    fn date_and_scalar_flags_in_one_namespace_are_written_to_toml() {
+        let mut save_data = NativeSaveData::new();
+        // 日付のフラグが値のフラグより前に来る順序でも書き出せる
+        save_data
+            .set_flag("npc.a_met_on", FlagValue::Date(GensoDate::new(112, 5, 3)))
+            .unwrap();
+        save_data
+            .set_flag("npc.b_visits", FlagValue::Int(3))
+            .unwrap();
+        save_data
+            .set_flag("npc.c_met", FlagValue::Bool(true))
+            .unwrap();
+        save_data
+            .set_flag("npc.d_name", FlagValue::Str("marisa".to_string()))
+            .unwrap();
+        save_data
+            .set_flag("npc.e_left_on", FlagValue::Date(GensoDate::new(112, 6, 1)))
+            .unwrap();
+
+        let content = save_data.to_toml_string().unwrap();
+        let loaded = NativeSaveData::from_toml_str(&content).unwrap();
+
+        assert_eq!(loaded.get_flags(), save_data.get_flags());
+        assert_eq!(
+            loaded.get_flags().get("npc.a_met_on"),
+            Some(&FlagValue::Date(GensoDate::new(112, 5, 3)))
+        );
+        assert_eq!(loaded.get_flags().get_int("npc.b_visits"), 3);
+    }
+
+    #[test]
+    fn keys_need_namespace_and_name() {
+        for key in [
+            "tutorial.finished",
+            "npc.met_marisa",
+            "npc.marisa.likes",
+            "a1.B_2",
+        ] {
+            assert!(is_valid_key(key), "{}", key);
+        }
+        for key in [
+            "",
+            "tutorial",
+            ".finished",
+            "npc.",
+            "npc..met",
+            "npc.met marisa",
+            "npc.霊夢",
+        ] {
+            assert!(!is_valid_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn flag_value_from_str() {
+        let cases = [
+            ("true", FlagValue::Bool(true)),
+            (" false ", FlagValue::Bool(false)),
+            ("-3", FlagValue::Int(-3)),
+            ("\"42\"", FlagValue::Str("42".to_string())),
+            ("\"\"", FlagValue::Str(String::new())),
+            ("112/5/3", FlagValue::Date(GensoDate::new(112, 5, 3))),
+            ("marisa", FlagValue::Str("marisa".to_string())),
+            // 数字でない部分があれば日付ではなく文字列
+            ("112/5/x", FlagValue::Str("112/5/x".to_string())),
+        ];
+
+        for (s, value) in cases {
+            assert_eq!(s.parse::<FlagValue>(), Ok(value), "{}", s);
+        }
+        assert!("".parse::<FlagValue>().is_err());
+        assert!("  ".parse::<FlagValue>().is_err());
+        assert_eq!(
+            "112/2/30".parse::<FlagValue>(),
+            Err("invalid date: 112/2/30".to_string())
+        );
+    }
+
+    #[test]
+    fn flag_condition_from_str() {
+        let cases = [
+            ("npc.met", FlagCondition::Truthy("npc.met".to_string())),
+            (" !npc.met ", FlagCondition::Falsy("npc.met".to_string())),
+            ("! npc.met", FlagCondition::Falsy("npc.met".to_string())),
+            (
+                "shop.visits >= 3",
+                FlagCondition::Compare("shop.visits".to_string(), CompareOp::Ge, FlagValue::Int(3)),
+            ),
+            (
+                "shop.visits<3",
+                FlagCondition::Compare("shop.visits".to_string(), CompareOp::Lt, FlagValue::Int(3)),
+            ),
+            (
+                "npc.name != \"marisa\"",
+                FlagCondition::Compare(
+                    "npc.name".to_string(),
+                    CompareOp::Ne,
+                    FlagValue::Str("marisa".to_string()),
+                ),
+            ),
+            // 一番左の演算子で分けるので、右辺に演算子を含められる
+            (
+                "npc.mood == a<=b",
+                FlagCondition::Compare(
+                    "npc.mood".to_string(),
+                    CompareOp::Eq,
+                    FlagValue::Str("a<=b".to_string()),
+                ),
+            ),
+        ];
+
+        for (s, condition) in cases {
+            assert_eq!(s.parse::<FlagCondition>(), Ok(condition), "{}", s);
+        }
+
+        for s in [
+            "",
+            "npc",
+            "!",
+            "npc met",
+            "== 3",
+            "npc.met ==",
+            "npc.met == 112/13/1",
+        ] {
+            assert!(s.parse::<FlagCondition>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn in_namespace_returns_only_that_namespace() {
+        let mut flags = StoryFlags::new();
+        for key in [
+            "npc.met_marisa",
+            "npc.marisa.likes",
+            "npcs.count",
+            "np.x",
+            "shop.visits",
+        ] {
+            flags.set(key, FlagValue::Bool(true)).unwrap();
+        }
+
+        let keys = flags
+            .in_namespace("npc")
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["npc.marisa.likes", "npc.met_marisa"]);
+
+        let keys = flags
+            .in_namespace("npc.marisa")
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["npc.marisa.likes"]);
+        assert_eq!(flags.in_namespace("tutorial").count(), 0);
+    }
+
+    #[test]
+    fn set_rejects_invalid_keys() {
+        let mut flags = StoryFlags::new();
+
+        assert_eq!(
+            flags.set("met", FlagValue::Bool(true)),
+            Err(FlagError::InvalidKey("met".to_string()))
+        );
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected_when_loading() {
+        let flags = toml::from_str::<StoryFlags>("\"npc.met\" = true\nvisits = 3");
+        assert!(flags
+            .unwrap_err()
+            .to_string()
+            .contains("invalid flag key: visits"));
+
+        let flags =
+            toml::from_str::<StoryFlags>("\"npc.met\" = true\n\"shop.visits\" = 3").unwrap();
+        assert_eq!(flags.len(), 2);
+        assert_eq!(flags.get_int("shop.visits"), 3);
+    }
+}
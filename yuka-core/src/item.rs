@@ -0,0 +1,539 @@
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    sync::RwLock,
+};
+
+/// 表示名が翻訳されていない言語では、この言語の表示名を使う
+pub const DEFAULT_LANGUAGE: &str = "ja";
+
+/// ゲームのデータにアイテムの定義が無いときに使う定義
+const BUILTIN_CATALOG: &str = include_str!("../data/items.toml");
+
+///
+/// アイテムを表す変わらない名前。セーブデータにはこの名前で記録する
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ItemId(String);
+
+impl ItemId {
+    pub fn new(id: &str) -> Self {
+        ItemId(id.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Display for ItemId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+///
+/// アイテムカタログ導入前のアイテム
+/// 古いセーブデータは表示名で記録しているので、ゲームのデータに関係なくidに置き換えられるよう残している
+///
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SoilItem {
+    Fuyodo,
+    Kurotsuchi,
+    Baiyodo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum FertilizerItem {
+    Aburakasu,
+    Gyohi,
+    ShimoGoe,
+    Chemical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Item {
+    Soil(SoilItem),
+    Fertilizer(FertilizerItem),
+}
+
+impl Item {
+    pub const ALL: [Item; 7] = [
+        Self::Soil(SoilItem::Fuyodo),
+        Self::Soil(SoilItem::Kurotsuchi),
+        Self::Soil(SoilItem::Baiyodo),
+        Self::Fertilizer(FertilizerItem::Aburakasu),
+        Self::Fertilizer(FertilizerItem::Gyohi),
+        Self::Fertilizer(FertilizerItem::ShimoGoe),
+        Self::Fertilizer(FertilizerItem::Chemical),
+    ];
+
+    ///
+    /// カタログ導入前のセーブデータに記録されていた表示名と、今のid
+    ///
+    fn legacy_entry(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Soil(SoilItem::Fuyodo) => ("腐葉土", "soil.fuyodo"),
+            Self::Soil(SoilItem::Kurotsuchi) => ("黒土", "soil.kurotsuchi"),
+            Self::Soil(SoilItem::Baiyodo) => ("培養土", "soil.baiyodo"),
+            Self::Fertilizer(FertilizerItem::Aburakasu) => ("油粕", "fertilizer.aburakasu"),
+            Self::Fertilizer(FertilizerItem::Gyohi) => ("魚肥", "fertilizer.gyohi"),
+            Self::Fertilizer(FertilizerItem::ShimoGoe) => ("下肥", "fertilizer.shimogoe"),
+            Self::Fertilizer(FertilizerItem::Chemical) => ("化学肥料", "fertilizer.chemical"),
+        }
+    }
+
+    pub fn legacy_name(&self) -> &'static str {
+        self.legacy_entry().0
+    }
+
+    pub fn id(&self) -> ItemId {
+        ItemId::new(self.legacy_entry().1)
+    }
+
+    pub fn from_legacy_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|item| item.legacy_name() == name)
+            .cloned()
+    }
+}
+
+impl From<Item> for ItemId {
+    fn from(item: Item) -> Self {
+        item.id()
+    }
+}
+
+///
+/// 古いセーブデータの表示名からidを引く。読み込んだカタログには関係しない
+///
+pub fn legacy_item_id(name: &str) -> Option<ItemId> {
+    Item::from_legacy_name(name).map(|item| item.id())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub id: ItemId,
+    pub category: String,
+    /// 言語ごとの表示名
+    pub name: BTreeMap<String, String>,
+    #[serde(default)]
+    pub description: BTreeMap<String, String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub price: u32,
+    /// 持てる数の上限。0なら上限なし
+    #[serde(default)]
+    pub stack_limit: usize,
+    /// idを変えたときの古いid
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub effect: BTreeMap<String, f64>,
+}
+
+impl ItemDefinition {
+    ///
+    /// 指定した言語の表示名。無ければ既定の言語、それも無ければid
+    ///
+    pub fn display_name(&self, language: &str) -> &str {
+        self.name
+            .get(language)
+            .or_else(|| self.name.get(DEFAULT_LANGUAGE))
+            .map_or(self.id.as_str(), String::as_str)
+    }
+
+    pub fn description(&self, language: &str) -> &str {
+        self.description
+            .get(language)
+            .or_else(|| self.description.get(DEFAULT_LANGUAGE))
+            .map_or("", String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CatalogError {
+    Parse(String),
+    DuplicateId(String),
+}
+
+impl Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse item catalog: {}", e),
+            Self::DuplicateId(id) => write!(f, "item id {} is defined more than once", id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CatalogFile {
+    #[serde(default)]
+    item: Vec<ItemDefinition>,
+}
+
+///
+/// ゲームのデータから読み込むアイテムの定義の一覧
+///
+#[derive(Debug, Clone, Default)]
+pub struct ItemCatalog {
+    /// データファイルに書かれた順
+    items: Vec<ItemDefinition>,
+    /// idとaliasesからitemsの位置を引く
+    index: HashMap<String, usize>,
+}
+
+impl ItemCatalog {
+    pub fn from_toml_str(s: &str) -> Result<Self, CatalogError> {
+        let file =
+            toml::from_str::<CatalogFile>(s).map_err(|e| CatalogError::Parse(e.to_string()))?;
+
+        let mut catalog = ItemCatalog::default();
+        for (position, item) in file.item.iter().enumerate() {
+            for key in
+                std::iter::once(item.id.as_str()).chain(item.aliases.iter().map(String::as_str))
+            {
+                if catalog.index.insert(key.to_string(), position).is_some() {
+                    return Err(CatalogError::DuplicateId(key.to_string()));
+                }
+            }
+        }
+        catalog.items = file.item;
+
+        Ok(catalog)
+    }
+
+    pub fn builtin() -> Self {
+        Self::from_toml_str(BUILTIN_CATALOG).expect("builtin item catalog is invalid")
+    }
+
+    ///
+    /// idか古いidで定義を引く
+    ///
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDefinition> {
+        self.index
+            .get(id.as_str())
+            .map(|position| &self.items[*position])
+    }
+
+    ///
+    /// 古いidを今のidにする。カタログに無いidはそのまま返す
+    ///
+    pub fn resolve(&self, id: &ItemId) -> ItemId {
+        self.get(id)
+            .map_or_else(|| id.clone(), |item| item.id.clone())
+    }
+
+    pub fn contains(&self, id: &ItemId) -> bool {
+        self.index.contains_key(id.as_str())
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ItemDefinition> {
+        self.items.iter()
+    }
+
+    pub fn in_category<'a>(
+        &'a self,
+        category: &'a str,
+    ) -> impl Iterator<Item = &'a ItemDefinition> {
+        self.items
+            .iter()
+            .filter(move |item| item.category == category)
+    }
+
+    ///
+    /// カタログに無いアイテムはidをそのまま表示する
+    ///
+    pub fn display_name(&self, id: &ItemId, language: &str) -> String {
+        self.get(id)
+            .map_or(id.as_str(), |item| item.display_name(language))
+            .to_string()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Noneのときは、最初に参照されたときに組み込みの定義を読み込む
+static ITEM_CATALOG: RwLock<Option<ItemCatalog>> = RwLock::new(None);
+
+pub fn set_item_catalog(catalog: ItemCatalog) {
+    *ITEM_CATALOG.write().unwrap() = Some(catalog);
+}
+
+pub fn with_item_catalog<F, R>(f: F) -> R
+where
+    F: FnOnce(&ItemCatalog) -> R,
+{
+    if let Some(catalog) = ITEM_CATALOG.read().unwrap().as_ref() {
+        return f(catalog);
+    }
+
+    f(ITEM_CATALOG
+        .write()
+        .unwrap()
+        .get_or_insert_with(ItemCatalog::builtin))
+}
+
+///
+/// 持っているアイテムの数。idの順に並ぶ
+///
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ItemManager {
+    items: BTreeMap<ItemId, usize>,
+}
+
+impl ItemManager {
+    pub fn new() -> Self {
+        ItemManager {
+            items: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_items<I: Into<ItemId>>(&mut self, item: I, count: usize) {
+        *self.items.entry(item.into()).or_insert(0) += count;
+    }
+
+    ///
+    /// 持っている数が上限 (0なら上限なし) を超えない分だけ足し、実際に足した数を返す
+    ///
+    pub fn add_items_up_to<I: Into<ItemId>>(
+        &mut self,
+        item: I,
+        count: usize,
+        limit: usize,
+    ) -> usize {
+        let bag_count = self.items.entry(item.into()).or_insert(0);
+        let added = if limit == 0 {
+            count
+        } else {
+            count.min(limit.saturating_sub(*bag_count))
+        };
+        *bag_count += added;
+        added
+    }
+
+    ///
+    /// カタログ導入前の表示名と古いidを今のidにする。同じidになったものは数を足す
+    ///
+    pub fn resolve_ids(&mut self, catalog: &ItemCatalog) {
+        for (id, count) in std::mem::take(&mut self.items) {
+            let id = legacy_item_id(id.as_str()).unwrap_or_else(|| catalog.resolve(&id));
+            *self.items.entry(id).or_insert(0) += count;
+        }
+    }
+
+    ///
+    /// カタログに定義が無いアイテム
+    ///
+    pub fn unknown_ids<'a>(&'a self, catalog: &'a ItemCatalog) -> impl Iterator<Item = &'a ItemId> {
+        self.items.keys().filter(move |id| !catalog.contains(id))
+    }
+
+    pub fn count(&self, item: &ItemId) -> usize {
+        self.items.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> std::collections::btree_map::Iter<'_, ItemId, usize> {
+        self.items.iter()
+    }
+
+    pub fn size(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = r#"
+[[item]]
+id = "seed.sunflower"
+category = "seed"
+price = 50
+stack_limit = 10
+name = { ja = "ひまわりの種", en = "Sunflower Seeds" }
+effect = { growth_days = 12.0 }
+
+[[item]]
+id = "tool.watering_can"
+category = "tool"
+name = { ja = "じょうろ" }
+"#;
+
+    #[test]
+    fn legacy_items_are_in_builtin_catalog() {
+        let catalog = ItemCatalog::builtin();
+
+        for item in Item::ALL {
+            assert!(
+                catalog.contains(&item.id()),
+                "{} is not in catalog",
+                item.id()
+            );
+            assert_eq!(
+                catalog.display_name(&item.id(), DEFAULT_LANGUAGE),
+                item.legacy_name()
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_names_are_resolved_without_catalog() {
+        assert_eq!(legacy_item_id("腐葉土"), Some(ItemId::new("soil.fuyodo")));
+        assert_eq!(
+            legacy_item_id("化学肥料"),
+            Some(ItemId::new("fertilizer.chemical"))
+        );
+        assert_eq!(legacy_item_id("soil.fuyodo"), None);
+        assert_eq!(
+            Item::from_legacy_name("下肥"),
+            Some(Item::Fertilizer(FertilizerItem::ShimoGoe))
+        );
+    }
+
+    #[test]
+    fn catalog_is_parsed_in_file_order() {
+        let catalog = ItemCatalog::from_toml_str(CATALOG).unwrap();
+
+        let ids = catalog
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["seed.sunflower", "tool.watering_can"]);
+
+        let seed = catalog.get(&ItemId::new("seed.sunflower")).unwrap();
+        assert_eq!(seed.price, 50);
+        assert_eq!(seed.stack_limit, 10);
+        assert_eq!(seed.effect.get("growth_days"), Some(&12.0));
+        assert_eq!(catalog.in_category("tool").count(), 1);
+    }
+
+    #[test]
+    fn optional_fields_have_defaults() {
+        let catalog = ItemCatalog::from_toml_str(CATALOG).unwrap();
+        let can = catalog.get(&ItemId::new("tool.watering_can")).unwrap();
+
+        assert_eq!(can.price, 0);
+        assert_eq!(can.stack_limit, 0);
+        assert_eq!(can.icon, None);
+        assert!(can.effect.is_empty());
+        assert_eq!(can.description("ja"), "");
+    }
+
+    #[test]
+    fn display_name_falls_back_to_default_language_and_id() {
+        let catalog = ItemCatalog::from_toml_str(CATALOG).unwrap();
+
+        let seed = ItemId::new("seed.sunflower");
+        let can = ItemId::new("tool.watering_can");
+        assert_eq!(catalog.display_name(&seed, "en"), "Sunflower Seeds");
+        assert_eq!(catalog.display_name(&can, "en"), "じょうろ");
+        assert_eq!(
+            catalog.display_name(&ItemId::new("unknown"), "ja"),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn duplicate_id_is_rejected() {
+        let duplicated = format!("{}{}", CATALOG, CATALOG);
+
+        assert_eq!(
+            ItemCatalog::from_toml_str(&duplicated).err(),
+            Some(CatalogError::DuplicateId("seed.sunflower".to_string()))
+        );
+        assert!(matches!(
+            ItemCatalog::from_toml_str("[[item]]\nid = 1"),
+            Err(CatalogError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn item_manager_counts_by_id() {
+        let mut items = ItemManager::new();
+        items.add_items(Item::Soil(SoilItem::Fuyodo), 2);
+        items.add_items(ItemId::new("soil.fuyodo"), 3);
+        items.add_items(ItemId::new("fertilizer.gyohi"), 1);
+
+        assert_eq!(items.count(&ItemId::new("soil.fuyodo")), 5);
+        assert_eq!(items.count(&ItemId::new("soil.baiyodo")), 0);
+        assert_eq!(items.size(), 2);
+
+        let ids = items.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["fertilizer.gyohi", "soil.fuyodo"]);
+    }
+
+    #[test]
+    fn item_manager_limits_total_count() {
+        let mut items = ItemManager::new();
+        let seed = ItemId::new("seed.sunflower");
+
+        assert_eq!(items.add_items_up_to(seed.clone(), 7, 10), 7);
+        assert_eq!(items.add_items_up_to(seed.clone(), 7, 10), 3);
+        assert_eq!(items.add_items_up_to(seed.clone(), 1, 10), 0);
+        assert_eq!(items.count(&seed), 10);
+        assert_eq!(items.add_items_up_to(seed.clone(), 5, 0), 5);
+    }
+
+    #[test]
+    fn old_ids_are_merged_into_current_id() {
+        let mut items = ItemManager::new();
+        items.add_items(ItemId::new("腐葉土"), 2);
+        items.add_items(ItemId::new("soil.fuyodo"), 3);
+        items.add_items(ItemId::new("unknown"), 1);
+
+        items.resolve_ids(&ItemCatalog::builtin());
+
+        assert_eq!(items.count(&ItemId::new("soil.fuyodo")), 5);
+        assert_eq!(items.count(&ItemId::new("腐葉土")), 0);
+        assert_eq!(items.count(&ItemId::new("unknown")), 1);
+        assert_eq!(items.size(), 2);
+    }
+
+    #[test]
+    fn legacy_names_are_resolved_with_game_catalog() {
+        let catalog = ItemCatalog::from_toml_str(
+            r#"
+[[item]]
+id = "soil.leaf_mold"
+category = "soil"
+aliases = ["soil.fuyodo"]
+name = { ja = "腐葉土" }
+"#,
+        )
+        .unwrap();
+        let mut items = ItemManager::new();
+        items.add_items(ItemId::new("油粕"), 2);
+        items.add_items(ItemId::new("soil.fuyodo"), 1);
+
+        items.resolve_ids(&catalog);
+
+        assert_eq!(items.count(&ItemId::new("fertilizer.aburakasu")), 2);
+        assert_eq!(items.count(&ItemId::new("soil.leaf_mold")), 1);
+        let unknown = items.unknown_ids(&catalog).collect::<Vec<_>>();
+        assert_eq!(unknown, [&ItemId::new("fertilizer.aburakasu")]);
+    }
+
+    #[test]
+    fn item_manager_is_saved_as_id_table() {
+        let mut items = ItemManager::new();
+        items.add_items(ItemId::new("soil.fuyodo"), 2);
+
+        assert_eq!(
+            toml::to_string(&items).unwrap(),
+            "[items]\n\"soil.fuyodo\" = 2\n"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+pub mod calendar;
+pub mod crypt;
+pub mod daily;
+pub mod dialogue;
+pub mod flag;
+pub mod item;
+pub mod save;
+pub mod settings;
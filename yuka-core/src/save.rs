@@ -0,0 +1,299 @@
+pub mod autosave;
+pub mod change;
+pub mod codec;
+pub mod compression;
+pub mod error;
+pub mod format;
+pub mod header;
+pub mod migration;
+pub mod scene;
+pub mod share;
+pub mod slot;
+pub mod statistics;
+pub mod storage;
+pub mod worker;
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar::{DateOutOfRange, GensoDate, GensoTime, TimeAction},
+    flag::{FlagError, FlagValue, StoryFlags},
+    item::{with_item_catalog, ItemId, ItemManager},
+};
+use change::{ChangeLog, SaveDataChange};
+use error::SaveError;
+use format::SaveFormat;
+use scene::{DialogueProgress, SceneRecord};
+use statistics::SaveStatistics;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NativeSaveData {
+    version: u32,
+    real_date: String,
+    items: ItemManager,
+    date: GensoDate,
+    time: GensoTime,
+    /// 日付が進んだのに、まだ日次処理をしていない最初の日付。Noneなら処理済み
+    /// 日次処理の前にセーブした場合も、ロード後に続きから処理できるようにセーブファイルに含める
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unprocessed_since: Option<GensoDate>,
+    flags: StoryFlags,
+    stats: SaveStatistics,
+    scene: SceneRecord,
+    /// 前回take_changesを呼んでからの変更。セーブファイルには含めない
+    #[serde(skip)]
+    changes: ChangeLog,
+}
+
+impl Default for NativeSaveData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NativeSaveData {
+    pub fn new() -> Self {
+        NativeSaveData {
+            version: migration::CURRENT_SAVE_DATA_VERSION,
+            items: ItemManager::new(),
+            date: GensoDate::new(112, 5, 1),
+            real_date: "None".to_string(),
+            time: GensoTime::morning(),
+            unprocessed_since: None,
+            flags: StoryFlags::new(),
+            stats: SaveStatistics::new(),
+            scene: SceneRecord::default(),
+            changes: ChangeLog::default(),
+        }
+    }
+
+    pub fn get_items(&self) -> &ItemManager {
+        &self.items
+    }
+
+    ///
+    /// 古いidは今のidにして、カタログのstack_limitを超えない分だけ足す
+    /// 実際に足した数を返す
+    ///
+    pub fn add_items<I: Into<ItemId>>(&mut self, item: I, count: usize) -> usize {
+        let (id, limit) = with_item_catalog(|catalog| {
+            let id = catalog.resolve(&item.into());
+            let limit = catalog.get(&id).map_or(0, |item| item.stack_limit);
+            (id, limit)
+        });
+
+        let added = self.items.add_items_up_to(id, count, limit);
+        self.stats.items_acquired += added as u64;
+        self.changes.record(SaveDataChange::Items);
+        added
+    }
+
+    pub fn get_date(&self) -> &GensoDate {
+        &self.date
+    }
+
+    pub fn get_time(&self) -> &GensoTime {
+        &self.time
+    }
+
+    ///
+    /// 時刻を進める。日付をまたいだ場合は日付も進め、進んだ日数を返す
+    /// 日付が表せる範囲を超える場合は、何も変えずにエラーを返す
+    ///
+    pub fn advance_time(&mut self, minutes: u32) -> Result<u32, DateOutOfRange> {
+        let mut time = self.time;
+        let days = time.advance(minutes);
+        // 一日は1440分なので、u32の分から求めた日数はi32に収まる
+        let date = self.date.try_add_day(days as i32)?;
+
+        self.time = time;
+        if days > 0 {
+            self.unprocessed_since.get_or_insert(self.date);
+            self.date = date;
+            self.stats.days_played += days;
+            self.changes.record(SaveDataChange::Date);
+        }
+        self.changes.record(SaveDataChange::Time);
+
+        Ok(days)
+    }
+
+    ///
+    /// 次の朝まで時間を進める
+    /// 日付が変わった後、朝になる前に眠った場合は同じ日付の朝になる
+    ///
+    pub fn sleep_until_morning(&mut self) -> Result<(), DateOutOfRange> {
+        if self.time >= GensoTime::morning() {
+            let date = self.date.try_add_day(1)?;
+            self.unprocessed_since.get_or_insert(self.date);
+            self.date = date;
+            self.stats.days_played += 1;
+            self.changes.record(SaveDataChange::Date);
+        }
+        self.time = GensoTime::morning();
+        self.changes.record(SaveDataChange::Time);
+
+        Ok(())
+    }
+
+    ///
+    /// 日次処理をしていない日付の範囲 (最初の日付, 現在の日付) を取り出し、処理済みにする
+    ///
+    pub fn take_unprocessed_days(&mut self) -> Option<(GensoDate, GensoDate)> {
+        self.unprocessed_since
+            .take()
+            .filter(|since| *since < self.date)
+            .map(|since| (since, self.date))
+    }
+
+    pub fn get_flags(&self) -> &StoryFlags {
+        &self.flags
+    }
+
+    pub fn set_flag(&mut self, key: &str, value: FlagValue) -> Result<(), FlagError> {
+        if self.flags.set(key, value.clone())?.as_ref() != Some(&value) {
+            self.changes.record(SaveDataChange::Flags);
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_flag(&mut self, key: &str) -> Option<FlagValue> {
+        let removed = self.flags.remove(key);
+        if removed.is_some() {
+            self.changes.record(SaveDataChange::Flags);
+        }
+
+        removed
+    }
+
+    pub fn advance_time_by_action(&mut self, action: TimeAction) -> Result<u32, DateOutOfRange> {
+        self.advance_time(action.minutes())
+    }
+
+    pub fn get_statistics(&self) -> &SaveStatistics {
+        &self.stats
+    }
+
+    pub fn add_playtime(&mut self, delta_secs: f64) {
+        self.stats.add_playtime(delta_secs);
+    }
+
+    pub fn record_flowers_grown(&mut self, count: u64) {
+        self.stats.flowers_grown += count;
+    }
+
+    ///
+    /// 新しく始めたとき、ロードしたときに呼ぶ
+    ///
+    pub fn begin_session(&mut self) {
+        self.stats.begin_session();
+    }
+
+    ///
+    /// 保存日時を記録する
+    /// 書き込むデータと、書き込みが成功した後の現在のセーブデータに対して呼ぶ
+    ///
+    pub fn mark_saved(&mut self) {
+        self.update_real_date();
+        self.stats.mark_saved();
+    }
+
+    pub fn get_scene(&self) -> &SceneRecord {
+        &self.scene
+    }
+
+    ///
+    /// シーンを移るときに呼ぶ。会話の途中だった記録は消える
+    ///
+    pub fn enter_scene(&mut self, path: &str) {
+        self.scene = SceneRecord::new(path);
+    }
+
+    pub fn set_dialogue_progress(&mut self, progress: Option<DialogueProgress>) {
+        self.scene.dialogue = progress;
+    }
+
+    pub fn get_real_date(&self) -> &str {
+        self.real_date.as_str()
+    }
+
+    pub fn update_real_date(&mut self) {
+        let date = chrono::Local::now();
+        self.real_date = format!("{}-{}-{}", date.year(), date.month(), date.day());
+    }
+
+    ///
+    /// セーブデータ全体が置き換わったとき (ロード、新規作成) に、すべてを変更済みにする
+    ///
+    pub fn mark_all_changed(&mut self) {
+        for change in SaveDataChange::ALL {
+            self.changes.notify(change);
+        }
+    }
+
+    ///
+    /// 最後にセーブしてから変更されているか
+    ///
+    pub fn is_dirty(&self) -> bool {
+        self.changes.is_dirty()
+    }
+
+    pub fn get_revision(&self) -> u64 {
+        self.changes.revision()
+    }
+
+    pub fn mark_clean(&mut self, revision: u64) {
+        self.changes.mark_saved(revision);
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    pub fn take_changes(&mut self) -> Vec<SaveDataChange> {
+        self.changes.take()
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    ///
+    /// 古いバージョンのセーブデータも現在のバージョンまでマイグレーションしてから読み込む
+    ///
+    pub fn from_toml_str(s: &str) -> Result<Self, SaveError> {
+        let value =
+            toml::from_str::<toml::Value>(s).map_err(|e| SaveError::Parse(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    ///
+    /// アイテムのidを変えた場合に備えて、持っているアイテムは今のidにしてから返す
+    ///
+    pub fn from_value(value: toml::Value) -> Result<Self, SaveError> {
+        let value = migration::migrate(value)?;
+
+        let mut save_data: Self = value
+            .try_into()
+            .map_err(|e| SaveError::Parse(e.to_string()))?;
+        with_item_catalog(|catalog| save_data.items.resolve_ids(catalog));
+
+        Ok(save_data)
+    }
+
+    pub fn from_bytes(buf: &[u8], format: SaveFormat) -> Result<Self, SaveError> {
+        Self::from_value(format.parse(buf)?)
+    }
+
+    pub fn to_bytes(&mut self, format: SaveFormat) -> Result<Vec<u8>, SaveError> {
+        self.version = migration::CURRENT_SAVE_DATA_VERSION;
+        format.serialize(self)
+    }
+
+    pub fn to_toml_string(&mut self) -> Result<String, SaveError> {
+        self.version = migration::CURRENT_SAVE_DATA_VERSION;
+        toml::to_string(self).map_err(|e| SaveError::Serialize(e.to_string()))
+    }
+}
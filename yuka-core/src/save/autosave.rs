@@ -0,0 +1,224 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// セーブ画面でオートセーブを表示するエントリの名前
+pub const AUTOSAVE_ENTRY: &str = "Autosave";
+
+/// オートセーブはこれらのスロットに順番に書き込む
+pub const AUTOSAVE_SLOTS: [&str; 3] = ["Autosave1", "Autosave2", "Autosave3"];
+
+/// シーン切り替えのオートセーブが続いたときに、書き込みを間引く間隔
+/// 日付の変更と終了時のオートセーブは間引かない
+pub const AUTOSAVE_MIN_INTERVAL: Duration = Duration::from_secs(30);
+
+///
+/// オートセーブ用に予約されたスロット名か
+/// 手動セーブでこれらの名前に書き込むことはできない
+///
+pub fn is_reserved_slot(slot: &str) -> bool {
+    slot.to_ascii_lowercase()
+        .starts_with(&AUTOSAVE_ENTRY.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutosavePolicy {
+    Off,
+    #[default]
+    Daily,
+    EverySceneChange,
+}
+
+impl AutosavePolicy {
+    pub fn allows(&self, trigger: AutosaveTrigger) -> bool {
+        match self {
+            Self::Off => false,
+            Self::Daily => trigger != AutosaveTrigger::SceneChange,
+            Self::EverySceneChange => true,
+        }
+    }
+}
+
+impl fmt::Display for AutosavePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Daily => write!(f, "daily"),
+            Self::EverySceneChange => write!(f, "every_scene_change"),
+        }
+    }
+}
+
+impl FromStr for AutosavePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "daily" => Ok(Self::Daily),
+            "every_scene_change" => Ok(Self::EverySceneChange),
+            _ => Err(format!("unknown autosave policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutosaveTrigger {
+    DayChange,
+    SceneChange,
+    Quit,
+}
+
+impl FromStr for AutosaveTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DayChange" => Ok(Self::DayChange),
+            "SceneChange" => Ok(Self::SceneChange),
+            "Quit" => Ok(Self::Quit),
+            _ => Err(format!("unknown autosave trigger: {}", s)),
+        }
+    }
+}
+
+///
+/// オートセーブを書き込むかどうか、どのスロットに書き込むかを決める
+///
+pub struct Autosaver {
+    policy: AutosavePolicy,
+    next: usize,
+    /// 最後にシーン切り替えで書き込んだ時刻
+    last_scene_change: Option<Instant>,
+}
+
+impl Default for Autosaver {
+    fn default() -> Self {
+        Self::new(AutosavePolicy::default())
+    }
+}
+
+impl Autosaver {
+    pub fn new(policy: AutosavePolicy) -> Self {
+        Autosaver {
+            policy,
+            next: 0,
+            last_scene_change: None,
+        }
+    }
+
+    pub fn policy(&self) -> AutosavePolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: AutosavePolicy) {
+        self.policy = policy;
+    }
+
+    ///
+    /// 既存のオートセーブの保存時刻から、次に書き込むスロットを決める
+    /// 空のスロットがあればそこから、なければ一番古いスロットから書き込む
+    ///
+    pub fn resume<I>(&mut self, saved_at: I)
+    where
+        I: IntoIterator<Item = Option<i64>>,
+    {
+        self.next = saved_at
+            .into_iter()
+            .take(AUTOSAVE_SLOTS.len())
+            .enumerate()
+            .min_by_key(|(_, saved_at)| saved_at.unwrap_or(i64::MIN))
+            .map_or(0, |(index, _)| index);
+    }
+
+    ///
+    /// オートセーブを書き込むべきなら、書き込み先のスロットを返す
+    /// シーン切り替えは、前回のシーン切り替えから間もない場合は書き込まない
+    ///
+    pub fn begin(&mut self, trigger: AutosaveTrigger, now: Instant) -> Option<&'static str> {
+        if !self.policy.allows(trigger) {
+            return None;
+        }
+
+        if trigger == AutosaveTrigger::SceneChange {
+            if let Some(last_scene_change) = self.last_scene_change {
+                if now.duration_since(last_scene_change) < AUTOSAVE_MIN_INTERVAL {
+                    return None;
+                }
+            }
+            self.last_scene_change = Some(now);
+        }
+
+        let slot = AUTOSAVE_SLOTS[self.next];
+        self.next = (self.next + 1) % AUTOSAVE_SLOTS.len();
+
+        Some(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_change_is_not_throttled_by_scene_change() {
+        let mut autosaver = Autosaver::new(AutosavePolicy::EverySceneChange);
+        let now = Instant::now();
+
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::SceneChange, now),
+            Some("Autosave1")
+        );
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::DayChange, now + Duration::from_secs(1)),
+            Some("Autosave2")
+        );
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::DayChange, now + Duration::from_secs(2)),
+            Some("Autosave3")
+        );
+    }
+
+    #[test]
+    fn scene_changes_are_throttled() {
+        let mut autosaver = Autosaver::new(AutosavePolicy::EverySceneChange);
+        let now = Instant::now();
+
+        assert!(autosaver.begin(AutosaveTrigger::SceneChange, now).is_some());
+        assert!(autosaver
+            .begin(AutosaveTrigger::SceneChange, now + Duration::from_secs(1))
+            .is_none());
+        assert!(autosaver
+            .begin(AutosaveTrigger::DayChange, now + Duration::from_secs(2))
+            .is_some());
+        assert!(autosaver
+            .begin(AutosaveTrigger::SceneChange, now + AUTOSAVE_MIN_INTERVAL)
+            .is_some());
+    }
+
+    #[test]
+    fn slots_are_used_in_turn() {
+        let mut autosaver = Autosaver::default();
+        autosaver.resume([Some(3), None, Some(1)]);
+
+        let now = Instant::now();
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::DayChange, now),
+            Some("Autosave2")
+        );
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::DayChange, now),
+            Some("Autosave3")
+        );
+        assert_eq!(
+            autosaver.begin(AutosaveTrigger::Quit, now),
+            Some("Autosave1")
+        );
+        assert_eq!(autosaver.begin(AutosaveTrigger::SceneChange, now), None);
+    }
+}
@@ -0,0 +1,67 @@
+///
+/// セーブデータのどの部分が変更されたか
+/// UI側はこれを見て必要な部分だけを更新する
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SaveDataChange {
+    Items,
+    Date,
+    Time,
+    Flags,
+}
+
+impl SaveDataChange {
+    /// セーブデータ全体が置き換わったときに通知する変更
+    pub const ALL: [SaveDataChange; 4] = [Self::Items, Self::Date, Self::Time, Self::Flags];
+}
+
+///
+/// 同じ変更は一度だけ記録する
+/// 変更のたびにrevisionを進め、最後にセーブしたrevisionと比べてセーブしていない変更があるかを判断する
+///
+#[derive(Debug, Clone, Default)]
+pub struct ChangeLog {
+    changes: Vec<SaveDataChange>,
+    revision: u64,
+    saved_revision: u64,
+}
+
+impl ChangeLog {
+    pub fn record(&mut self, change: SaveDataChange) {
+        self.revision += 1;
+        self.notify(change);
+    }
+
+    ///
+    /// 内容は変わっていないが、UIに再表示させたいときに使う
+    ///
+    pub fn notify(&mut self, change: SaveDataChange) {
+        if !self.changes.contains(&change) {
+            self.changes.push(change);
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    ///
+    /// revisionの時点の内容が書き込まれた
+    /// 書き込み中に変更があった場合は、セーブしていない変更が残る
+    ///
+    pub fn mark_saved(&mut self, revision: u64) {
+        self.saved_revision = self.saved_revision.max(revision);
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.revision > self.saved_revision
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn take(&mut self) -> Vec<SaveDataChange> {
+        std::mem::take(&mut self.changes)
+    }
+}
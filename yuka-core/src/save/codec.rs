@@ -0,0 +1,225 @@
+use serde::Deserialize;
+
+use super::{
+    compression::Compression,
+    error::SaveError,
+    format::{SaveFormat, DEFAULT_SAVE_FORMAT},
+    header::SlotHeader,
+    NativeSaveData,
+};
+use crate::crypt::{self, OpenError, SaveKeys};
+
+/// セーブファイルに書き込むときの圧縮方式
+pub const DEFAULT_COMPRESSION: Compression = Compression::Deflate;
+
+///
+/// 書き込むときの形式と圧縮方式。どちらもヘッダに記録されるので、スロットごとに違っていてよい
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub format: SaveFormat,
+    pub compression: Compression,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            format: DEFAULT_SAVE_FORMAT,
+            compression: DEFAULT_COMPRESSION,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveFileFormat {
+    /// 認証導入前の古いフォーマット
+    Legacy,
+    Sealed,
+}
+
+///
+/// 認証導入前の古いフォーマットを復号する関数
+/// 古い暗号化の実装はバインディング側にあるので、呼び出し側から渡してもらう
+///
+pub type LegacyDecrypt = fn(&[u8]) -> Result<String, String>;
+
+pub struct Decoded {
+    pub data: NativeSaveData,
+    pub format: SaveFileFormat,
+}
+
+///
+/// 本体を読むためにヘッダから必要な項目だけを取り出す
+/// スロット一覧用の項目が欠けていても、本体は読めるようにする
+///
+#[derive(Deserialize, Default)]
+struct Envelope {
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    format: SaveFormat,
+}
+
+pub fn encode(keys: &SaveKeys, save_data: &mut NativeSaveData) -> Result<Vec<u8>, SaveError> {
+    encode_with(keys, save_data, EncodeOptions::default())
+}
+
+pub fn encode_with(
+    keys: &SaveKeys,
+    save_data: &mut NativeSaveData,
+    options: EncodeOptions,
+) -> Result<Vec<u8>, SaveError> {
+    let content = save_data.to_bytes(options.format)?;
+
+    let mut header = SlotHeader::from_save_data(save_data);
+    header.compression = options.compression;
+    header.format = options.format;
+
+    Ok(crypt::seal(
+        keys,
+        &header.to_toml_string()?,
+        &options.compression.compress(&content),
+    ))
+}
+
+///
+/// 復号して、マイグレーション前の値として読む
+///
+pub fn decrypt(
+    keys: &SaveKeys,
+    buf: &[u8],
+    legacy: LegacyDecrypt,
+) -> Result<(toml::Value, SaveFileFormat), SaveError> {
+    match crypt::open(keys, buf) {
+        Ok((header, body)) => {
+            let envelope = match header {
+                Some(header) => toml::from_str::<Envelope>(&header)
+                    .map_err(|e| SaveError::Parse(e.to_string()))?,
+                None => Envelope::default(),
+            };
+
+            let body = envelope.compression.decompress(&body)?;
+
+            Ok((envelope.format.parse(&body)?, SaveFileFormat::Sealed))
+        }
+        Err(OpenError::Legacy) => {
+            let content = legacy(buf).map_err(SaveError::Decrypt)?;
+
+            Ok((
+                SaveFormat::Toml.parse(content.as_bytes())?,
+                SaveFileFormat::Legacy,
+            ))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn decode(keys: &SaveKeys, buf: &[u8], legacy: LegacyDecrypt) -> Result<Decoded, SaveError> {
+    let (value, format) = decrypt(keys, buf, legacy)?;
+
+    Ok(Decoded {
+        data: NativeSaveData::from_value(value)?,
+        format,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flag::FlagValue, item::ItemId};
+
+    const LEGACY_CONTENT: &str = r#"
+real_date = "None"
+
+[items.items]
+"腐葉土" = 2
+"#;
+
+    fn keys() -> SaveKeys {
+        SaveKeys::derive(b"yuka-core/save/codec/tests")
+    }
+
+    fn legacy(buf: &[u8]) -> Result<String, String> {
+        if buf == b"legacy" {
+            Ok(LEGACY_CONTENT.to_string())
+        } else {
+            Err("not a legacy save".to_string())
+        }
+    }
+
+    fn save_data() -> NativeSaveData {
+        let mut save_data = NativeSaveData::new();
+        save_data.add_items(ItemId::new("soil.kurotsuchi"), 3);
+        save_data
+            .set_flag("npc.met_marisa", FlagValue::Bool(true))
+            .unwrap();
+        save_data
+    }
+
+    #[test]
+    fn encoded_save_is_decoded() {
+        let buf = encode(&keys(), &mut save_data()).unwrap();
+        let decoded = decode(&keys(), &buf, legacy).unwrap();
+
+        assert_eq!(decoded.format, SaveFileFormat::Sealed);
+        assert_eq!(
+            decoded
+                .data
+                .get_items()
+                .count(&ItemId::new("soil.kurotsuchi")),
+            3
+        );
+        assert_eq!(
+            decoded.data.get_flags().get("npc.met_marisa"),
+            Some(&FlagValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn header_records_options() {
+        let options = EncodeOptions {
+            format: SaveFormat::Json,
+            compression: Compression::None,
+        };
+        let buf = encode_with(&keys(), &mut save_data(), options).unwrap();
+
+        let (header, _) = crypt::open(&keys(), &buf).unwrap();
+        let header = toml::from_str::<SlotHeader>(&header.unwrap()).unwrap();
+        assert_eq!(header.format, SaveFormat::Json);
+        assert_eq!(header.compression, Compression::None);
+        assert_eq!(header.item_total, 3);
+
+        assert!(decode(&keys(), &buf, legacy).is_ok());
+    }
+
+    #[test]
+    fn legacy_save_is_decrypted_by_binding() {
+        let decoded = decode(&keys(), b"legacy", legacy).unwrap();
+
+        assert_eq!(decoded.format, SaveFileFormat::Legacy);
+        assert_eq!(
+            decoded.data.get_items().count(&ItemId::new("soil.fuyodo")),
+            2
+        );
+    }
+
+    #[test]
+    fn legacy_decrypt_error_is_reported() {
+        assert!(matches!(
+            decode(&keys(), b"broken", legacy),
+            Err(SaveError::Decrypt(_))
+        ));
+    }
+
+    #[test]
+    fn tampered_save_is_rejected() {
+        let mut buf = encode(&keys(), &mut save_data()).unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 1;
+
+        assert!(matches!(
+            decode(&keys(), &buf, legacy),
+            Err(SaveError::Corrupted(OpenError::Tampered))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde::{Deserialize, Serialize};
+
+use super::error::SaveError;
+
+///
+/// 展開後の大きさの上限。壊れたデータや細工されたデータで際限なくメモリを使わないようにする
+///
+pub const MAX_DECOMPRESSED_LEN: u64 = 16 * 1024 * 1024;
+
+///
+/// セーブデータ本体の圧縮方式
+/// idはファイルや共有コードに記録されるので、既存の値を変えてはいけない
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+}
+
+impl Compression {
+    pub fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Deflate => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::None => data.to_vec(),
+            Self::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                // Vecへの書き込みは失敗しない
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, SaveError> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Deflate => {
+                let mut output = Vec::new();
+                DeflateDecoder::new(data)
+                    .take(MAX_DECOMPRESSED_LEN + 1)
+                    .read_to_end(&mut output)
+                    .map_err(|e| SaveError::Decompress(e.to_string()))?;
+
+                if output.len() as u64 > MAX_DECOMPRESSED_LEN {
+                    return Err(SaveError::Decompress(format!(
+                        "decompressed data exceeds {} bytes",
+                        MAX_DECOMPRESSED_LEN
+                    )));
+                }
+
+                Ok(output)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Deflate => write!(f, "deflate"),
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "deflate" => Ok(Self::Deflate),
+            _ => Err(format!("unknown compression: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = "腐葉土 = 3\n".repeat(100).into_bytes();
+
+        for compression in [Compression::None, Compression::Deflate] {
+            let compressed = compression.compress(&data);
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+        }
+        assert!(Compression::Deflate.compress(&data).len() < data.len());
+    }
+
+    #[test]
+    fn ids_are_stable() {
+        for compression in [Compression::None, Compression::Deflate] {
+            assert_eq!(Compression::from_id(compression.id()), Some(compression));
+            assert_eq!(compression.to_string().parse(), Ok(compression));
+        }
+        assert_eq!(Compression::Deflate.id(), 1);
+        assert_eq!(Compression::from_id(2), None);
+        assert!("zip".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn data_up_to_limit_is_decompressed() {
+        let data = vec![0; MAX_DECOMPRESSED_LEN as usize];
+        let compressed = Compression::Deflate.compress(&data);
+
+        assert_eq!(
+            Compression::Deflate.decompress(&compressed).unwrap().len(),
+            data.len()
+        );
+    }
+
+    #[test]
+    fn data_over_limit_is_rejected() {
+        // 小さな圧縮データが上限を超えて展開されるもの
+        let data = vec![0; MAX_DECOMPRESSED_LEN as usize + 1];
+        let compressed = Compression::Deflate.compress(&data);
+        assert!(compressed.len() < 64 * 1024);
+
+        match Compression::Deflate.decompress(&compressed) {
+            Err(SaveError::Decompress(e)) => assert!(e.contains("exceeds"), "{}", e),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("oversized data was decompressed"),
+        }
+    }
+
+    #[test]
+    fn corrupt_deflate_stream_is_rejected() {
+        // BTYPE = 11 は予約されていて、使われない
+        assert!(matches!(
+            Compression::Deflate.decompress(&[0xff; 16]),
+            Err(SaveError::Decompress(_))
+        ));
+
+        let compressed = Compression::Deflate.compress(&"腐葉土".repeat(100).into_bytes());
+        assert!(matches!(
+            Compression::Deflate.decompress(&compressed[..compressed.len() / 2]),
+            Err(SaveError::Decompress(_))
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+use super::{migration::MigrationError, share::ShareCodeError};
+use crate::crypt::OpenError;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Decrypt(String),
+    Corrupted(OpenError),
+    UnsupportedFormat(u8),
+    Serialize(String),
+    Parse(String),
+    Schema(MigrationError),
+    NoCurrentSave,
+    ReservedSlot(String),
+    UnknownSlot(String),
+    /// ファイル名に使えない文字を含むスロットのid
+    InvalidSlot(String),
+    Decompress(String),
+    ShareCode(ShareCodeError),
+    /// 鍵の素を渡さずにビルドしたので、暗号化も復号もできない
+    NoSaveKey,
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Decrypt(e) => write!(f, "failed to decrypt legacy save data: {}", e),
+            Self::Corrupted(e) => write!(f, "{}", e),
+            Self::UnsupportedFormat(v) => write!(f, "unsupported save file format version {}", v),
+            Self::Serialize(e) => write!(f, "failed to serialize save data: {}", e),
+            Self::Parse(e) => write!(f, "failed to parse save data: {}", e),
+            Self::Schema(e) => write!(f, "incompatible save data: {}", e),
+            Self::NoCurrentSave => write!(f, "no save data is currently loaded"),
+            Self::ReservedSlot(slot) => write!(f, "{} is reserved for autosave", slot),
+            Self::UnknownSlot(slot) => write!(f, "no such save slot: {}", slot),
+            Self::InvalidSlot(slot) => write!(f, "invalid save slot id: {}", slot),
+            Self::Decompress(e) => write!(f, "failed to decompress save data: {}", e),
+            Self::ShareCode(e) => write!(f, "invalid save code: {}", e),
+            Self::NoSaveKey => write!(
+                f,
+                "save key is not available; build with {} set",
+                crate::crypt::SAVE_SECRET_ENV
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<MigrationError> for SaveError {
+    fn from(e: MigrationError) -> Self {
+        SaveError::Schema(e)
+    }
+}
+
+impl From<ShareCodeError> for SaveError {
+    fn from(e: ShareCodeError) -> Self {
+        SaveError::ShareCode(e)
+    }
+}
+
+impl From<OpenError> for SaveError {
+    fn from(e: OpenError) -> Self {
+        match e {
+            OpenError::UnsupportedFormat(v) => SaveError::UnsupportedFormat(v),
+            e => SaveError::Corrupted(e),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use super::{error::SaveError, NativeSaveData};
+
+///
+/// セーブデータ本体の書き込み形式
+/// 読み込むときは、どの形式でも一度toml::Valueにしてからマイグレーションする
+/// そのため、どの形式でもマップのキーは文字列にしておく必要がある
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaveFormat {
+    /// 人が読んで調べられる形式
+    #[default]
+    Toml,
+    Json,
+    /// 小さくて速いバイナリ形式
+    Cbor,
+}
+
+///
+/// ビルドごとに選ぶ、新しく書き込むときの形式
+///
+#[cfg(feature = "binary-saves")]
+pub const DEFAULT_SAVE_FORMAT: SaveFormat = SaveFormat::Cbor;
+#[cfg(all(feature = "json-saves", not(feature = "binary-saves")))]
+pub const DEFAULT_SAVE_FORMAT: SaveFormat = SaveFormat::Json;
+#[cfg(not(any(feature = "json-saves", feature = "binary-saves")))]
+pub const DEFAULT_SAVE_FORMAT: SaveFormat = SaveFormat::Toml;
+
+impl SaveFormat {
+    pub const ALL: [SaveFormat; 3] = [Self::Toml, Self::Json, Self::Cbor];
+
+    pub fn serialize(self, save_data: &NativeSaveData) -> Result<Vec<u8>, SaveError> {
+        match self {
+            Self::Toml => toml::to_string(save_data)
+                .map(String::into_bytes)
+                .map_err(|e| SaveError::Serialize(e.to_string())),
+            Self::Json => {
+                serde_json::to_vec(save_data).map_err(|e| SaveError::Serialize(e.to_string()))
+            }
+            Self::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::ser::into_writer(save_data, &mut buf)
+                    .map_err(|e| SaveError::Serialize(e.to_string()))?;
+                Ok(buf)
+            }
+        }
+    }
+
+    ///
+    /// マイグレーション前の値として読む
+    ///
+    pub fn parse(self, buf: &[u8]) -> Result<toml::Value, SaveError> {
+        match self {
+            Self::Toml => {
+                let s = std::str::from_utf8(buf).map_err(|e| SaveError::Parse(e.to_string()))?;
+                toml::from_str(s).map_err(|e| SaveError::Parse(e.to_string()))
+            }
+            Self::Json => serde_json::from_slice(buf).map_err(|e| SaveError::Parse(e.to_string())),
+            Self::Cbor => {
+                ciborium::de::from_reader(buf).map_err(|e| SaveError::Parse(e.to_string()))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SaveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Toml => write!(f, "toml"),
+            Self::Json => write!(f, "json"),
+            Self::Cbor => write!(f, "cbor"),
+        }
+    }
+}
+
+impl std::str::FromStr for SaveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            "cbor" => Ok(Self::Cbor),
+            _ => Err(format!("unknown save format: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        calendar::GensoDate,
+        crypt::SaveKeys,
+        flag::FlagValue,
+        item::ItemId,
+        save::{
+            codec::{self, EncodeOptions},
+            compression::Compression,
+            scene::DialogueProgress,
+        },
+    };
+
+    fn no_legacy(_: &[u8]) -> Result<String, String> {
+        Err("not a legacy save".to_string())
+    }
+
+    fn save_data() -> NativeSaveData {
+        let mut save_data = NativeSaveData::new();
+        save_data.add_items(ItemId::new("soil.kurotsuchi"), 3);
+        save_data
+            .set_flag("npc.a_met_on", FlagValue::Date(GensoDate::new(112, 5, 3)))
+            .unwrap();
+        save_data
+            .set_flag("npc.b_visits", FlagValue::Int(2))
+            .unwrap();
+        save_data
+            .set_flag("npc.c_name", FlagValue::Str("marisa".to_string()))
+            .unwrap();
+        save_data.set_dialogue_progress(Some(DialogueProgress {
+            path: "res://resources/scenario/sample.txt".to_string(),
+            position: 4,
+        }));
+        // 統計の導入前に作られたセーブデータ
+        save_data.stats.created_at = None;
+        save_data.stats.last_saved_at = None;
+        save_data
+    }
+
+    #[test]
+    fn every_format_and_compression_round_trips() {
+        let keys = SaveKeys::derive(b"yuka-core/save/format/tests");
+        let expected = save_data().to_toml_string().unwrap();
+
+        for format in SaveFormat::ALL {
+            for compression in [Compression::None, Compression::Deflate] {
+                let options = EncodeOptions {
+                    format,
+                    compression,
+                };
+                let buf = codec::encode_with(&keys, &mut save_data(), options).unwrap();
+                let mut decoded = codec::decode(&keys, &buf, no_legacy).unwrap().data;
+
+                assert_eq!(
+                    decoded.to_toml_string().unwrap(),
+                    expected,
+                    "{} with {:?}",
+                    format,
+                    compression
+                );
+                assert!(decoded.get_statistics().created_at.is_none());
+                assert_eq!(decoded.get_scene().dialogue.as_ref().unwrap().position, 4);
+            }
+        }
+    }
+
+    #[test]
+    fn format_names_round_trip() {
+        for format in SaveFormat::ALL {
+            assert_eq!(format.to_string().parse::<SaveFormat>(), Ok(format));
+        }
+        assert!("yaml".parse::<SaveFormat>().is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use std::{io::Read, path::Path};
+
+use super::{compression::Compression, error::SaveError, format::SaveFormat, NativeSaveData};
+use crate::{calendar::GensoDate, crypt};
+
+///
+/// スロット一覧の表示に必要な情報だけをまとめたもの
+/// セーブファイルの平文ヘッダに格納されるので、本体を復号しなくても読める
+///
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlotHeader {
+    pub schema_version: u32,
+    pub real_date: String,
+    pub saved_at: i64,
+    pub playtime_secs: u64,
+    /// 統計の導入前のヘッダには存在しない
+    #[serde(default)]
+    pub days_played: u32,
+    pub item_total: usize,
+    /// 本体の圧縮方式。圧縮を導入する前のヘッダには存在しないので、無圧縮として読む
+    #[serde(default)]
+    pub compression: Compression,
+    /// 本体の形式。形式を選べるようになる前のヘッダには存在しないので、TOMLとして読む
+    #[serde(default)]
+    pub format: SaveFormat,
+    // TOMLではテーブルを値より後に置く必要があるので最後にする
+    pub date: GensoDate,
+}
+
+impl SlotHeader {
+    pub fn from_save_data(save_data: &NativeSaveData) -> Self {
+        let stats = save_data.get_statistics();
+
+        SlotHeader {
+            schema_version: save_data.get_version(),
+            real_date: save_data.get_real_date().to_string(),
+            saved_at: stats
+                .last_saved_at
+                .unwrap_or_else(chrono::Utc::now)
+                .timestamp(),
+            playtime_secs: stats.playtime_secs,
+            days_played: stats.days_played,
+            item_total: save_data.get_items().iter().map(|(_, count)| count).sum(),
+            compression: Compression::None,
+            format: SaveFormat::Toml,
+            date: *save_data.get_date(),
+        }
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, SaveError> {
+        toml::to_string(self).map_err(|e| SaveError::Serialize(e.to_string()))
+    }
+}
+
+///
+/// ファイル先頭のヘッダ部分だけを読む
+/// ヘッダを持たない古いフォーマットの場合はNoneを返す
+///
+pub fn read_slot_header(path: &Path) -> Result<Option<SlotHeader>, SaveError> {
+    let mut file = std::fs::File::open(path)?;
+
+    let mut prefix = Vec::with_capacity(crypt::PREFIX_LEN);
+    file.by_ref()
+        .take(crypt::PREFIX_LEN as u64)
+        .read_to_end(&mut prefix)?;
+
+    let header_len = match crypt::parse_prefix(&prefix) {
+        Ok(Some(len)) => len,
+        Ok(None) | Err(crypt::OpenError::Legacy) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut header = Vec::with_capacity(header_len);
+    file.take(header_len as u64).read_to_end(&mut header)?;
+    if header.len() < header_len {
+        return Err(crypt::OpenError::Truncated.into());
+    }
+
+    let header =
+        String::from_utf8(header).map_err(|_| SaveError::from(crypt::OpenError::InvalidUtf8))?;
+
+    toml::from_str(&header)
+        .map(Some)
+        .map_err(|e| SaveError::Parse(e.to_string()))
+}
@@ -0,0 +1,504 @@
+use toml::{value::Table, Value};
+
+use crate::item::legacy_item_id;
+
+/// versionフィールドが存在しないセーブデータはすべてv1として扱う
+pub const LEGACY_SAVE_DATA_VERSION: u32 = 1;
+pub const CURRENT_SAVE_DATA_VERSION: u32 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationError {
+    NotATable,
+    InvalidVersion(String),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NotATable => write!(f, "save data root is not a table"),
+            Self::InvalidVersion(v) => write!(f, "invalid save data version: {}", v),
+            Self::UnsupportedVersion(v) => write!(
+                f,
+                "save data version {} is newer than supported version {}",
+                v, CURRENT_SAVE_DATA_VERSION
+            ),
+        }
+    }
+}
+
+type Migration = fn(&mut Table);
+
+///
+/// MIGRATIONS[n] は v(n + 1) -> v(n + 2) の変換
+/// 新しいバージョンを追加するときは末尾に追加して CURRENT_SAVE_DATA_VERSION を上げる
+///
+static MIGRATIONS: [Migration; (CURRENT_SAVE_DATA_VERSION - 1) as usize] = [
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+];
+
+pub fn read_version(table: &Table) -> Result<u32, MigrationError> {
+    match table.get("version") {
+        None => Ok(LEGACY_SAVE_DATA_VERSION),
+        Some(Value::Integer(v)) if *v >= LEGACY_SAVE_DATA_VERSION as i64 => Ok(*v as u32),
+        Some(v) => Err(MigrationError::InvalidVersion(v.to_string())),
+    }
+}
+
+pub fn migrate(value: Value) -> Result<Value, MigrationError> {
+    let mut table = match value {
+        Value::Table(table) => table,
+        _ => return Err(MigrationError::NotATable),
+    };
+
+    let version = read_version(&table)?;
+    if version > CURRENT_SAVE_DATA_VERSION {
+        return Err(MigrationError::UnsupportedVersion(version));
+    }
+
+    for (index, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip((version - LEGACY_SAVE_DATA_VERSION) as usize)
+    {
+        migration(&mut table);
+        table.insert(
+            "version".to_string(),
+            Value::Integer(index as i64 + LEGACY_SAVE_DATA_VERSION as i64 + 1),
+        );
+    }
+
+    Ok(Value::Table(table))
+}
+
+fn insert_if_missing(table: &mut Table, key: &str, value: Value) {
+    if !table.contains_key(key) {
+        table.insert(key.to_string(), value);
+    }
+}
+
+///
+/// v1: バージョン管理導入前のセーブデータ
+/// items, date, real_date は途中で追加されたので、古いものには存在しないことがある
+///
+fn migrate_v1_to_v2(table: &mut Table) {
+    insert_if_missing(table, "real_date", Value::String("None".to_string()));
+
+    let mut items = Table::new();
+    items.insert("items".to_string(), Value::Table(Table::new()));
+    insert_if_missing(table, "items", Value::Table(items));
+
+    let mut date = Table::new();
+    date.insert("season".to_string(), Value::Integer(112));
+    date.insert("month".to_string(), Value::Integer(5));
+    date.insert("day".to_string(), Value::Integer(1));
+    insert_if_missing(table, "date", Value::Table(date));
+}
+
+///
+/// v3: 時刻 (time) を追加
+///
+fn migrate_v2_to_v3(table: &mut Table) {
+    let mut time = Table::new();
+    time.insert("hour".to_string(), Value::Integer(6));
+    time.insert("minute".to_string(), Value::Integer(0));
+    insert_if_missing(table, "time", Value::Table(time));
+}
+
+///
+/// v4: ストーリーのフラグ (flags) を追加
+///
+fn migrate_v3_to_v4(table: &mut Table) {
+    insert_if_missing(table, "flags", Value::Table(Table::new()));
+}
+
+///
+/// v5: プレイ時間などの記録 (stats) を追加
+/// それまでの記録は残っていないので、すべて0から数える
+///
+fn migrate_v4_to_v5(table: &mut Table) {
+    insert_if_missing(table, "stats", Value::Table(Table::new()));
+}
+
+///
+/// v6: セーブしたときのシーン (scene) を追加
+/// それまではホーム画面でしかセーブできなかったので、ホームに戻す
+///
+fn migrate_v5_to_v6(table: &mut Table) {
+    let mut scene = Table::new();
+    scene.insert(
+        "path".to_string(),
+        Value::String(super::scene::HOME_SCENE.to_string()),
+    );
+    insert_if_missing(table, "scene", Value::Table(scene));
+}
+
+///
+/// v7: 持っているアイテムを表示名ではなくアイテムのidで記録する
+/// 知らない名前はそのまま残す。同じidになったものは数を足す
+///
+fn migrate_v6_to_v7(table: &mut Table) {
+    let items = match table
+        .get_mut("items")
+        .and_then(|items| items.get_mut("items"))
+        .and_then(Value::as_table_mut)
+    {
+        Some(items) => items,
+        None => return,
+    };
+
+    let mut migrated = Table::new();
+    for (name, count) in std::mem::take(items) {
+        // ゲームのデータで置き換えたカタログに古い表示名が無くても読めるよう、固定の対応表を使う
+        let id = legacy_item_id(&name).map_or(name, |id| id.to_string());
+
+        match (migrated.get_mut(&id), &count) {
+            (Some(Value::Integer(total)), Value::Integer(n)) => *total += n,
+            _ => {
+                migrated.insert(id, count);
+            }
+        }
+    }
+    *items = migrated;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        calendar::{GensoDate, GensoTime},
+        flag::FlagValue,
+        item::ItemId,
+        save::NativeSaveData,
+    };
+
+    /// バージョン管理導入前。アイテムは表示名で記録されている
+    const V1_SAVE: &str = r#"
+real_date = "2021-07-01 12:00:00"
+
+[items.items]
+"腐葉土" = 3
+"油粕" = 1
+
+[date]
+season = 112
+month = 6
+day = 12
+"#;
+
+    /// date, itemsが追加される前のセーブデータ
+    const V1_MINIMAL_SAVE: &str = r#"
+real_date = "None"
+"#;
+
+    const V2_SAVE: &str = r#"
+version = 2
+real_date = "2021-07-02 12:00:00"
+
+[items.items]
+"黒土" = 2
+
+[date]
+season = 112
+month = 6
+day = 13
+"#;
+
+    const V3_SAVE: &str = r#"
+version = 3
+real_date = "2021-07-03 12:00:00"
+
+[items.items]
+"培養土" = 4
+
+[date]
+season = 112
+month = 6
+day = 14
+
+[time]
+hour = 18
+minute = 30
+"#;
+
+    const V4_SAVE: &str = r#"
+version = 4
+real_date = "2021-07-04 12:00:00"
+
+[items.items]
+"魚肥" = 5
+
+[date]
+season = 112
+month = 6
+day = 15
+
+[time]
+hour = 9
+minute = 0
+
+[flags]
+"npc.met_marisa" = true
+"shop.visits" = 3
+"#;
+
+    const V5_SAVE: &str = r#"
+version = 5
+real_date = "2021-07-05 12:00:00"
+
+[items.items]
+"下肥" = 6
+
+[date]
+season = 112
+month = 6
+day = 16
+
+[time]
+hour = 12
+minute = 0
+
+[flags]
+"shop.visits" = 4
+
+[stats]
+playtime_secs = 3600
+session_count = 2
+days_played = 15
+items_acquired = 20
+flowers_grown = 1
+"#;
+
+    const V6_SAVE: &str = r#"
+version = 6
+real_date = "2021-07-06 12:00:00"
+
+[items.items]
+"化学肥料" = 7
+"腐葉土" = 1
+
+[date]
+season = 112
+month = 6
+day = 17
+
+[time]
+hour = 21
+minute = 0
+
+[flags]
+
+[stats]
+playtime_secs = 7200
+
+[scene]
+path = "res://scene/field/Field.tscn"
+
+[scene.dialogue]
+path = "res://resources/dialogue/field.txt"
+position = 2
+"#;
+
+    fn load(fixture: &str) -> NativeSaveData {
+        let save_data = NativeSaveData::from_toml_str(fixture).unwrap();
+        assert_eq!(save_data.get_version(), CURRENT_SAVE_DATA_VERSION);
+        save_data
+    }
+
+    fn item_count(save_data: &NativeSaveData, id: &str) -> usize {
+        save_data.get_items().count(&ItemId::new(id))
+    }
+
+    #[test]
+    fn v1_save_is_migrated() {
+        let save_data = load(V1_SAVE);
+
+        assert_eq!(*save_data.get_date(), GensoDate::new(112, 6, 12));
+        assert_eq!(*save_data.get_time(), GensoTime::morning());
+        assert_eq!(item_count(&save_data, "soil.fuyodo"), 3);
+        assert_eq!(item_count(&save_data, "fertilizer.aburakasu"), 1);
+        assert_eq!(save_data.get_items().size(), 2);
+        assert!(save_data.get_flags().is_empty());
+        assert_eq!(save_data.get_statistics().session_count, 0);
+        assert_eq!(save_data.get_scene().path, super::super::scene::HOME_SCENE);
+    }
+
+    #[test]
+    fn v1_save_without_items_and_date_is_migrated() {
+        let save_data = load(V1_MINIMAL_SAVE);
+
+        assert_eq!(*save_data.get_date(), GensoDate::new(112, 5, 1));
+        assert_eq!(save_data.get_items().size(), 0);
+    }
+
+    #[test]
+    fn v2_save_is_migrated() {
+        let save_data = load(V2_SAVE);
+
+        assert_eq!(*save_data.get_time(), GensoTime::morning());
+        assert_eq!(item_count(&save_data, "soil.kurotsuchi"), 2);
+    }
+
+    #[test]
+    fn v3_save_is_migrated() {
+        let save_data = load(V3_SAVE);
+
+        assert_eq!(*save_data.get_time(), GensoTime::new(18, 30));
+        assert_eq!(item_count(&save_data, "soil.baiyodo"), 4);
+        assert!(save_data.get_flags().is_empty());
+    }
+
+    #[test]
+    fn v4_save_is_migrated() {
+        let save_data = load(V4_SAVE);
+
+        assert_eq!(item_count(&save_data, "fertilizer.gyohi"), 5);
+        assert_eq!(
+            save_data.get_flags().get("npc.met_marisa"),
+            Some(&FlagValue::Bool(true))
+        );
+        assert_eq!(save_data.get_statistics().playtime_secs, 0);
+        assert_eq!(save_data.get_statistics().created_at, None);
+    }
+
+    #[test]
+    fn v5_save_is_migrated() {
+        let save_data = load(V5_SAVE);
+
+        assert_eq!(item_count(&save_data, "fertilizer.shimogoe"), 6);
+        assert_eq!(save_data.get_statistics().playtime_secs, 3600);
+        assert_eq!(save_data.get_statistics().days_played, 15);
+        assert_eq!(save_data.get_scene().path, super::super::scene::HOME_SCENE);
+        assert_eq!(save_data.get_scene().dialogue, None);
+    }
+
+    #[test]
+    fn v6_save_is_migrated() {
+        let save_data = load(V6_SAVE);
+
+        assert_eq!(item_count(&save_data, "fertilizer.chemical"), 7);
+        assert_eq!(item_count(&save_data, "soil.fuyodo"), 1);
+        assert_eq!(save_data.get_scene().path, "res://scene/field/Field.tscn");
+        assert_eq!(
+            save_data.get_scene().dialogue.as_ref().map(|d| d.position),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn legacy_and_new_item_keys_are_merged() {
+        let save_data = load(
+            r#"
+version = 6
+real_date = "None"
+
+[items.items]
+"腐葉土" = 3
+"soil.fuyodo" = 2
+"謎の種" = 1
+
+[date]
+season = 112
+month = 6
+day = 17
+
+[time]
+hour = 6
+minute = 0
+
+[flags]
+
+[stats]
+
+[scene]
+path = "res://scene/home/Home.tscn"
+"#,
+        );
+
+        assert_eq!(item_count(&save_data, "soil.fuyodo"), 5);
+        assert_eq!(item_count(&save_data, "謎の種"), 1);
+    }
+
+    #[test]
+    fn legacy_item_names_are_migrated_without_catalog() {
+        let value = migrate(toml::from_str(V1_SAVE).unwrap()).unwrap();
+        let items = value
+            .get("items")
+            .and_then(|items| items.get("items"))
+            .and_then(Value::as_table)
+            .unwrap();
+
+        let keys = items.keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(keys, ["fertilizer.aburakasu", "soil.fuyodo"]);
+    }
+
+    #[test]
+    fn old_item_ids_are_resolved_when_loading_current_version() {
+        let save_data = load(
+            r#"
+version = 7
+real_date = "None"
+
+[items.items]
+"油粕" = 4
+"fertilizer.aburakasu" = 1
+
+[date]
+season = 112
+month = 6
+day = 17
+
+[time]
+hour = 6
+minute = 0
+
+[flags]
+
+[stats]
+
+[scene]
+path = "res://scene/home/Home.tscn"
+"#,
+        );
+
+        assert_eq!(item_count(&save_data, "fertilizer.aburakasu"), 5);
+        assert_eq!(save_data.get_items().size(), 1);
+    }
+
+    #[test]
+    fn every_version_is_stamped_in_order() {
+        let value = migrate(toml::from_str(V1_MINIMAL_SAVE).unwrap()).unwrap();
+        let table = value.as_table().unwrap();
+
+        assert_eq!(read_version(table), Ok(CURRENT_SAVE_DATA_VERSION));
+        for key in ["items", "date", "time", "flags", "stats", "scene"] {
+            assert!(table.contains_key(key), "{} is missing", key);
+        }
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let value =
+            toml::from_str(&format!("version = {}", CURRENT_SAVE_DATA_VERSION + 1)).unwrap();
+        assert_eq!(
+            migrate(value),
+            Err(MigrationError::UnsupportedVersion(
+                CURRENT_SAVE_DATA_VERSION + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_version_is_rejected() {
+        let value = toml::from_str("version = \"1\"").unwrap();
+        assert!(matches!(
+            migrate(value),
+            Err(MigrationError::InvalidVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+pub const HOME_SCENE: &str = "res://scene/home/Home.tscn";
+
+///
+/// 会話の途中でセーブしたときの、会話ファイルと何番目のSerifか
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialogueProgress {
+    pub path: String,
+    /// 条件で取り除く前の、会話ファイルの中での位置
+    pub position: usize,
+}
+
+///
+/// セーブしたときにいたシーン。ロードするとここに戻る
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneRecord {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialogue: Option<DialogueProgress>,
+}
+
+impl Default for SceneRecord {
+    fn default() -> Self {
+        SceneRecord::new(HOME_SCENE)
+    }
+}
+
+impl SceneRecord {
+    pub fn new(path: &str) -> Self {
+        SceneRecord {
+            path: path.to_string(),
+            dialogue: None,
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use super::{
+    compression::Compression,
+    error::SaveError,
+    migration::{MigrationError, CURRENT_SAVE_DATA_VERSION, LEGACY_SAVE_DATA_VERSION},
+    NativeSaveData,
+};
+use crate::crypt::{self, SaveKeys};
+
+///
+/// セーブデータを貼り付けて共有するためのテキストコード
+///
+/// YUKA1:<base64url(payload)>
+/// payload: | code version (1) | compression (1) | schema version (4, LE) | body | checksum (16) |
+///
+/// bodyはTOMLを圧縮したもの。checksumはcode versionからbodyまでに対して計算するので、
+/// 壊れたコードや書き換えられたコードは読み込まない
+///
+pub const CODE_PREFIX: &str = "YUKA1:";
+pub const CODE_VERSION: u8 = 1;
+
+const PAYLOAD_HEADER_LEN: usize = 1 + 1 + 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareCodeError {
+    MissingPrefix,
+    InvalidBase64,
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownCompression(u8),
+    Tampered,
+    InvalidUtf8,
+}
+
+impl std::fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingPrefix => write!(f, "code does not start with {}", CODE_PREFIX),
+            Self::InvalidBase64 => write!(f, "code contains invalid characters"),
+            Self::Truncated => write!(f, "code is truncated"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported code version {}", v),
+            Self::UnknownCompression(v) => write!(f, "unknown compression {}", v),
+            Self::Tampered => write!(f, "code is corrupted or has been modified"),
+            Self::InvalidUtf8 => write!(f, "code body is not valid UTF-8"),
+        }
+    }
+}
+
+pub fn export(
+    keys: &SaveKeys,
+    save_data: &mut NativeSaveData,
+    compression: Compression,
+) -> Result<String, SaveError> {
+    let content = save_data.to_toml_string()?;
+    let body = compression.compress(content.as_bytes());
+
+    let mut payload = Vec::with_capacity(PAYLOAD_HEADER_LEN + body.len() + crypt::CHECKSUM_LEN);
+    payload.push(CODE_VERSION);
+    payload.push(compression.id());
+    payload.extend_from_slice(&save_data.get_version().to_le_bytes());
+    payload.extend_from_slice(&body);
+
+    let checksum = crypt::checksum(keys, &payload);
+    payload.extend_from_slice(&checksum);
+
+    Ok(format!(
+        "{}{}",
+        CODE_PREFIX,
+        base64::encode_config(&payload, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+///
+/// コードを読み込み、現在のバージョンまでマイグレーションしたセーブデータを返す
+/// チャットなどで改行や空白が入っても読めるよう、空白文字は無視する
+///
+pub fn import(keys: &SaveKeys, code: &str) -> Result<NativeSaveData, SaveError> {
+    let code = code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let encoded = code
+        .strip_prefix(CODE_PREFIX)
+        .ok_or(ShareCodeError::MissingPrefix)?;
+
+    let payload = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| ShareCodeError::InvalidBase64)?;
+    if payload.len() < PAYLOAD_HEADER_LEN + crypt::CHECKSUM_LEN {
+        return Err(ShareCodeError::Truncated.into());
+    }
+
+    let (signed, checksum) = payload.split_at(payload.len() - crypt::CHECKSUM_LEN);
+    if signed[0] != CODE_VERSION {
+        return Err(ShareCodeError::UnsupportedVersion(signed[0]).into());
+    }
+    if !crypt::verify_checksum(keys, signed, checksum) {
+        return Err(ShareCodeError::Tampered.into());
+    }
+
+    let compression =
+        Compression::from_id(signed[1]).ok_or(ShareCodeError::UnknownCompression(signed[1]))?;
+
+    let mut version = [0; 4];
+    version.copy_from_slice(&signed[2..PAYLOAD_HEADER_LEN]);
+    let version = u32::from_le_bytes(version);
+    if version > CURRENT_SAVE_DATA_VERSION {
+        return Err(MigrationError::UnsupportedVersion(version).into());
+    }
+    if version < LEGACY_SAVE_DATA_VERSION {
+        return Err(MigrationError::InvalidVersion(version.to_string()).into());
+    }
+
+    let body = compression.decompress(&signed[PAYLOAD_HEADER_LEN..])?;
+    let content = String::from_utf8(body).map_err(|_| ShareCodeError::InvalidUtf8)?;
+
+    NativeSaveData::from_toml_str(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flag::FlagValue, item::ItemId};
+
+    fn keys() -> SaveKeys {
+        SaveKeys::derive(b"yuka-core/save/share/tests")
+    }
+
+    fn save_data() -> NativeSaveData {
+        let mut save_data = NativeSaveData::new();
+        save_data.add_items(ItemId::new("soil.kurotsuchi"), 3);
+        save_data
+            .set_flag("npc.met_marisa", FlagValue::Bool(true))
+            .unwrap();
+        save_data
+    }
+
+    fn payload(code: &str) -> Vec<u8> {
+        base64::decode_config(&code[CODE_PREFIX.len()..], base64::URL_SAFE_NO_PAD).unwrap()
+    }
+
+    ///
+    /// チェックサムを付け直して、正しく署名されたコードにする
+    ///
+    fn resign(mut payload: Vec<u8>) -> String {
+        payload.truncate(payload.len() - crypt::CHECKSUM_LEN);
+        let checksum = crypt::checksum(&keys(), &payload);
+        payload.extend_from_slice(&checksum);
+
+        format!(
+            "{}{}",
+            CODE_PREFIX,
+            base64::encode_config(&payload, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    fn share_code_error(code: &str) -> ShareCodeError {
+        match import(&keys(), code) {
+            Err(SaveError::ShareCode(e)) => e,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("invalid code was imported"),
+        }
+    }
+
+    #[test]
+    fn code_round_trips_with_and_without_compression() {
+        for compression in [Compression::None, Compression::Deflate] {
+            let code = export(&keys(), &mut save_data(), compression).unwrap();
+            assert!(code.starts_with(CODE_PREFIX));
+
+            let imported = import(&keys(), &code).unwrap();
+            assert_eq!(
+                imported.get_items().count(&ItemId::new("soil.kurotsuchi")),
+                3
+            );
+            assert!(imported.get_flags().get_bool("npc.met_marisa"));
+        }
+    }
+
+    #[test]
+    fn whitespace_in_code_is_ignored() {
+        let code = export(&keys(), &mut save_data(), Compression::Deflate).unwrap();
+        let wrapped = code
+            .as_bytes()
+            .chunks(20)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n ");
+
+        assert!(import(&keys(), &wrapped).is_ok());
+    }
+
+    #[test]
+    fn flipped_checksum_byte_is_tampered() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+        let mut payload = payload(&code);
+        *payload.last_mut().unwrap() ^= 1;
+        let code = format!(
+            "{}{}",
+            CODE_PREFIX,
+            base64::encode_config(&payload, base64::URL_SAFE_NO_PAD)
+        );
+
+        assert_eq!(share_code_error(&code), ShareCodeError::Tampered);
+    }
+
+    #[test]
+    fn code_from_other_keys_is_tampered() {
+        let other = SaveKeys::derive(b"yuka-core/save/share/other");
+        let code = export(&other, &mut save_data(), Compression::None).unwrap();
+
+        assert_eq!(share_code_error(&code), ShareCodeError::Tampered);
+    }
+
+    #[test]
+    fn code_without_prefix_is_rejected() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+
+        assert_eq!(
+            share_code_error(&code[CODE_PREFIX.len()..]),
+            ShareCodeError::MissingPrefix
+        );
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        assert_eq!(
+            share_code_error("YUKA1:not*base64!"),
+            ShareCodeError::InvalidBase64
+        );
+    }
+
+    #[test]
+    fn truncated_payload_is_rejected() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+        let payload = payload(&code);
+        let truncated = format!(
+            "{}{}",
+            CODE_PREFIX,
+            base64::encode_config(
+                &payload[..PAYLOAD_HEADER_LEN + crypt::CHECKSUM_LEN - 1],
+                base64::URL_SAFE_NO_PAD
+            )
+        );
+
+        assert_eq!(share_code_error(&truncated), ShareCodeError::Truncated);
+        assert_eq!(share_code_error(CODE_PREFIX), ShareCodeError::Truncated);
+    }
+
+    #[test]
+    fn unknown_code_version_is_rejected() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+        let mut payload = payload(&code);
+        payload[0] = CODE_VERSION + 1;
+
+        assert_eq!(
+            share_code_error(&resign(payload)),
+            ShareCodeError::UnsupportedVersion(CODE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn unknown_compression_is_rejected() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+        let mut payload = payload(&code);
+        payload[1] = 0xff;
+
+        assert_eq!(
+            share_code_error(&resign(payload)),
+            ShareCodeError::UnknownCompression(0xff)
+        );
+    }
+
+    #[test]
+    fn newer_schema_version_is_rejected() {
+        let code = export(&keys(), &mut save_data(), Compression::None).unwrap();
+        let mut payload = payload(&code);
+        payload[2..PAYLOAD_HEADER_LEN]
+            .copy_from_slice(&(CURRENT_SAVE_DATA_VERSION + 1).to_le_bytes());
+
+        assert!(matches!(
+            import(&keys(), &resign(payload)),
+            Err(SaveError::Schema(MigrationError::UnsupportedVersion(v)))
+                if v == CURRENT_SAVE_DATA_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{autosave, error::SaveError, storage};
+
+/// セーブファイルの拡張子
+pub const SLOT_EXTENSION: &str = "sav";
+
+/// スロットの一覧と表示名を記録するファイル
+pub const SLOT_INDEX_FILE: &str = "slots.toml";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotEntry {
+    /// ファイル名に使う識別子。作成後は変わらない
+    pub id: String,
+    /// プレイヤーが付ける名前
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SlotIndex {
+    #[serde(default, rename = "slot")]
+    slots: Vec<SlotEntry>,
+}
+
+///
+/// スロットのidとして使える文字列か
+/// ファイル名になるので英数字と_だけを許す
+///
+pub fn is_valid_slot_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+///
+/// セーブディレクトリの中のスロットを管理する
+/// 一覧はディレクトリ内のslots.tomlに記録し、操作のたびに読み書きする
+///
+pub struct SlotRegistry {
+    dir: PathBuf,
+}
+
+impl SlotRegistry {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        SlotRegistry { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, SLOT_EXTENSION))
+    }
+
+    ///
+    /// 外から渡されたidのパス。セーブディレクトリの外を指さないよう、使えないidは弾く
+    ///
+    pub fn checked_path(&self, id: &str) -> Result<PathBuf, SaveError> {
+        if is_valid_slot_id(id) {
+            Ok(self.path(id))
+        } else {
+            Err(SaveError::InvalidSlot(id.to_string()))
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join(SLOT_INDEX_FILE)
+    }
+
+    fn read_index(&self) -> Result<SlotIndex, SaveError> {
+        match fs::read_to_string(self.index_path()) {
+            Ok(content) => toml::from_str(&content).map_err(|e| SaveError::Parse(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SlotIndex::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_index(&self, index: &SlotIndex) -> Result<(), SaveError> {
+        let content = toml::to_string(index).map_err(|e| SaveError::Serialize(e.to_string()))?;
+        storage::write_atomic(&self.index_path(), content.as_bytes(), 0)
+    }
+
+    fn update_index<F, R>(&self, f: F) -> Result<R, SaveError>
+    where
+        F: FnOnce(&mut SlotIndex) -> Result<R, SaveError>,
+    {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut index = self.read_index()?;
+        let result = f(&mut index)?;
+        self.write_index(&index)?;
+
+        Ok(result)
+    }
+
+    ///
+    /// 手動セーブのスロットを作成順に返す。オートセーブは含まない
+    ///
+    pub fn list(&self) -> Result<Vec<SlotEntry>, SaveError> {
+        Ok(self.read_index()?.slots)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<SlotEntry>, SaveError> {
+        Ok(self.list()?.into_iter().find(|slot| slot.id == id))
+    }
+
+    fn next_id(index: &SlotIndex) -> String {
+        (1..)
+            .map(|n| format!("slot{}", n))
+            .find(|id| index.slots.iter().all(|slot| &slot.id != id))
+            .unwrap()
+    }
+
+    ///
+    /// 新しいスロットを登録する。ファイルは最初にセーブしたときに作られる
+    /// nameを省略した場合はidをそのまま名前にする
+    ///
+    pub fn create(&self, name: Option<&str>) -> Result<SlotEntry, SaveError> {
+        self.update_index(|index| {
+            let id = Self::next_id(index);
+            let entry = SlotEntry {
+                name: name.unwrap_or(&id).to_string(),
+                id,
+            };
+
+            index.slots.push(entry.clone());
+            Ok(entry)
+        })
+    }
+
+    ///
+    /// スロットをバックアップごと削除する
+    ///
+    pub fn delete(&self, id: &str) -> Result<(), SaveError> {
+        self.update_index(|index| {
+            let position = Self::position(index, id)?;
+
+            for generation in 0..=storage::BACKUP_GENERATIONS {
+                match fs::remove_file(storage::generation_path(&self.path(id), generation)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => (),
+                }
+            }
+
+            index.slots.remove(position);
+            Ok(())
+        })
+    }
+
+    ///
+    /// スロットの複製を元のスロットのすぐ後ろに作る
+    ///
+    pub fn duplicate(&self, id: &str) -> Result<SlotEntry, SaveError> {
+        self.update_index(|index| {
+            let position = Self::position(index, id)?;
+            let entry = SlotEntry {
+                id: Self::next_id(index),
+                name: format!("{} のコピー", index.slots[position].name),
+            };
+
+            let from = self.path(id);
+            if from.exists() {
+                fs::copy(&from, self.path(&entry.id))?;
+            }
+
+            index.slots.insert(position + 1, entry.clone());
+            Ok(entry)
+        })
+    }
+
+    pub fn rename(&self, id: &str, name: &str) -> Result<(), SaveError> {
+        self.update_index(|index| {
+            let position = Self::position(index, id)?;
+            index.slots[position].name = name.to_string();
+            Ok(())
+        })
+    }
+
+    ///
+    /// 登録されていないセーブファイルを、そのファイル名をidとしてスロットに加える
+    /// セーブディレクトリ導入前の、カレントディレクトリに置かれていたスロットの移行に使う
+    ///
+    pub fn import(&self, id: &str, legacy_path: &Path) -> Result<bool, SaveError> {
+        if !is_valid_slot_id(id) || autosave::is_reserved_slot(id) || !legacy_path.exists() {
+            return Ok(false);
+        }
+
+        self.update_index(|index| {
+            if index.slots.iter().any(|slot| slot.id == id) {
+                return Ok(false);
+            }
+
+            for generation in 0..=storage::BACKUP_GENERATIONS {
+                let from = storage::generation_path(legacy_path, generation);
+                if from.exists() {
+                    // 別のファイルシステムかもしれないので、コピーしてから消す
+                    fs::copy(&from, storage::generation_path(&self.path(id), generation))?;
+                    fs::remove_file(&from)?;
+                }
+            }
+
+            index.slots.push(SlotEntry {
+                id: id.to_string(),
+                name: id.to_string(),
+            });
+            Ok(true)
+        })
+    }
+
+    fn position(index: &SlotIndex, id: &str) -> Result<usize, SaveError> {
+        index
+            .slots
+            .iter()
+            .position(|slot| slot.id == id)
+            .ok_or_else(|| SaveError::UnknownSlot(id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(registry: &SlotRegistry) -> Vec<String> {
+        registry
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|slot| slot.id)
+            .collect()
+    }
+
+    #[test]
+    fn slot_ids_are_file_names() {
+        assert!(is_valid_slot_id("slot1"));
+        assert!(is_valid_slot_id("Entry_1"));
+        assert!(!is_valid_slot_id(""));
+        assert!(!is_valid_slot_id("../x"));
+        assert!(!is_valid_slot_id("a/b"));
+        assert!(!is_valid_slot_id("slot 1"));
+
+        let registry = SlotRegistry::new("saves");
+        assert_eq!(
+            registry.checked_path("slot1").unwrap(),
+            Path::new("saves/slot1.sav")
+        );
+        assert!(matches!(
+            registry.checked_path("../x"),
+            Err(SaveError::InvalidSlot(id)) if id == "../x"
+        ));
+    }
+
+    #[test]
+    fn create_registers_slots_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path().join("saves"));
+        assert!(registry.list().unwrap().is_empty());
+
+        let first = registry.create(None).unwrap();
+        let second = registry.create(Some("ゆか")).unwrap();
+
+        assert_eq!(
+            first,
+            SlotEntry {
+                id: "slot1".to_string(),
+                name: "slot1".to_string()
+            }
+        );
+        assert_eq!(second.id, "slot2");
+        assert_eq!(second.name, "ゆか");
+        assert_eq!(ids(&registry), ["slot1", "slot2"]);
+        assert_eq!(registry.get("slot2").unwrap(), Some(second));
+        assert_eq!(registry.get("slot3").unwrap(), None);
+        // ファイルは最初のセーブまで作られない
+        assert!(!registry.path("slot1").exists());
+    }
+
+    #[test]
+    fn next_id_reuses_deleted_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path());
+        for _ in 0..3 {
+            registry.create(None).unwrap();
+        }
+
+        registry.delete("slot2").unwrap();
+        assert_eq!(registry.create(None).unwrap().id, "slot2");
+        assert_eq!(registry.create(None).unwrap().id, "slot4");
+        assert_eq!(ids(&registry), ["slot1", "slot3", "slot2", "slot4"]);
+    }
+
+    #[test]
+    fn delete_removes_file_and_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path());
+        let entry = registry.create(None).unwrap();
+        let other = registry.create(None).unwrap();
+        let path = registry.path(&entry.id);
+        for generation in 0..=2 {
+            fs::write(storage::generation_path(&path, generation), "save").unwrap();
+        }
+        fs::write(registry.path(&other.id), "save").unwrap();
+
+        registry.delete(&entry.id).unwrap();
+
+        for generation in 0..=storage::BACKUP_GENERATIONS {
+            assert!(!storage::generation_path(&path, generation).exists());
+        }
+        assert!(registry.path(&other.id).exists());
+        assert_eq!(ids(&registry), ["slot2"]);
+        assert!(matches!(
+            registry.delete(&entry.id),
+            Err(SaveError::UnknownSlot(id)) if id == "slot1"
+        ));
+    }
+
+    #[test]
+    fn duplicate_is_placed_after_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path());
+        registry.create(Some("はじめ")).unwrap();
+        registry.create(Some("つぎ")).unwrap();
+        fs::write(registry.path("slot1"), "save 1").unwrap();
+
+        let copy = registry.duplicate("slot1").unwrap();
+
+        assert_eq!(copy.id, "slot3");
+        assert_eq!(copy.name, "はじめ のコピー");
+        assert_eq!(ids(&registry), ["slot1", "slot3", "slot2"]);
+        assert_eq!(
+            fs::read_to_string(registry.path("slot3")).unwrap(),
+            "save 1"
+        );
+
+        // まだセーブしていないスロットはファイル無しで複製する
+        let empty = registry.duplicate("slot2").unwrap();
+        assert!(!registry.path(&empty.id).exists());
+        assert!(matches!(
+            registry.duplicate("slot9"),
+            Err(SaveError::UnknownSlot(_))
+        ));
+    }
+
+    #[test]
+    fn rename_changes_only_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path());
+        let entry = registry.create(None).unwrap();
+
+        registry.rename(&entry.id, "花畑").unwrap();
+
+        assert_eq!(
+            registry.get(&entry.id).unwrap(),
+            Some(SlotEntry {
+                id: entry.id,
+                name: "花畑".to_string()
+            })
+        );
+        assert!(matches!(
+            registry.rename("slot9", "花畑"),
+            Err(SaveError::UnknownSlot(_))
+        ));
+    }
+
+    #[test]
+    fn import_moves_legacy_slot_with_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path().join("saves"));
+        let legacy = dir.path().join("Entry1");
+        fs::write(&legacy, "save").unwrap();
+        fs::write(storage::generation_path(&legacy, 1), "save 1").unwrap();
+
+        assert!(registry.import("Entry1", &legacy).unwrap());
+
+        assert!(!legacy.exists());
+        assert!(!storage::generation_path(&legacy, 1).exists());
+        let path = registry.path("Entry1");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "save");
+        assert_eq!(
+            fs::read_to_string(storage::generation_path(&path, 1)).unwrap(),
+            "save 1"
+        );
+        assert_eq!(
+            registry.get("Entry1").unwrap(),
+            Some(SlotEntry {
+                id: "Entry1".to_string(),
+                name: "Entry1".to_string()
+            })
+        );
+
+        // 登録済みのidは取り込まない
+        fs::write(&legacy, "other").unwrap();
+        assert!(!registry.import("Entry1", &legacy).unwrap());
+        assert!(legacy.exists());
+        assert_eq!(ids(&registry), ["Entry1"]);
+    }
+
+    #[test]
+    fn import_skips_missing_invalid_and_reserved_slots() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = SlotRegistry::new(dir.path().join("saves"));
+        let legacy = dir.path().join("legacy");
+        fs::write(&legacy, "save").unwrap();
+
+        assert!(!registry
+            .import("Entry2", &dir.path().join("Entry2"))
+            .unwrap());
+        assert!(!registry.import("../x", &legacy).unwrap());
+        assert!(!registry
+            .import(autosave::AUTOSAVE_SLOTS[0], &legacy)
+            .unwrap());
+
+        assert!(legacy.exists());
+        assert!(registry.list().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+///
+/// プレイ時間やこれまでの記録
+/// 古いセーブデータには存在しない項目があるので、足りない項目は既定値で読み込む
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveStatistics {
+    pub playtime_secs: u64,
+    /// 統計の導入前に作られたセーブデータではNone
+    /// JSONなどでnullとして書くとtoml::Valueで読めないので、Noneは書かない
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_saved_at: Option<DateTime<Utc>>,
+    pub session_count: u32,
+    pub days_played: u32,
+    pub items_acquired: u64,
+    pub flowers_grown: u64,
+    /// 1秒に満たないプレイ時間。次の加算に持ち越す
+    #[serde(skip)]
+    playtime_fraction: f64,
+}
+
+impl SaveStatistics {
+    pub fn new() -> Self {
+        SaveStatistics {
+            created_at: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// フレームごとの経過時間を足す
+    ///
+    pub fn add_playtime(&mut self, delta_secs: f64) {
+        if !delta_secs.is_finite() || delta_secs <= 0.0 {
+            return;
+        }
+
+        self.playtime_fraction += delta_secs;
+        let whole = self.playtime_fraction.trunc();
+        self.playtime_secs += whole as u64;
+        self.playtime_fraction -= whole;
+    }
+
+    pub fn begin_session(&mut self) {
+        self.session_count += 1;
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.last_saved_at = Some(Utc::now());
+    }
+}
+
+///
+/// プレイ時間を "時間:分" の形にする
+///
+pub fn format_playtime(secs: u64) -> String {
+    format!("{}:{:02}", secs / 3600, secs / 60 % 60)
+}
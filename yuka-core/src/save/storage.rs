@@ -0,0 +1,243 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use super::error::SaveError;
+
+/// スロットごとに保持するバックアップの世代数 (slot.bak1 ~ slot.bakN)
+pub const BACKUP_GENERATIONS: usize = 3;
+
+pub struct Recovered<T> {
+    pub data: T,
+    /// 0ならスロット本体、1以上ならそのバックアップ世代から復旧した
+    pub generation: usize,
+}
+
+impl<T> Recovered<T> {
+    pub fn is_recovered_from_backup(&self) -> bool {
+        self.generation > 0
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+pub fn temp_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".tmp")
+}
+
+///
+/// generation 0 はスロット本体を指す
+///
+pub fn generation_path(path: &Path, generation: usize) -> PathBuf {
+    if generation == 0 {
+        path.to_path_buf()
+    } else {
+        with_suffix(path, &format!(".bak{}", generation))
+    }
+}
+
+fn rotate_backups(path: &Path, generations: usize) -> std::io::Result<()> {
+    if generations == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    for generation in (1..generations).rev() {
+        let from = generation_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, generation_path(path, generation + 1))?;
+        }
+    }
+
+    // 本体はrenameせずにコピーする
+    // 書き込みが途中で失敗しても本体が残るようにするため
+    fs::copy(path, generation_path(path, 1))?;
+
+    Ok(())
+}
+
+///
+/// 一時ファイルに書き込んでからrenameで置き換える
+/// 置き換える前に、既存のスロットをバックアップとしてずらしておく
+///
+pub fn write_atomic(path: &Path, content: &[u8], generations: usize) -> Result<(), SaveError> {
+    let temp = temp_path(path);
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(content)?;
+        file.flush()?;
+        file.sync_all()?;
+        drop(file);
+
+        rotate_backups(path, generations)?;
+        fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result.map_err(SaveError::from)
+}
+
+///
+/// スロット本体から順に新しいバックアップを試し、最初に読み込めたものを返す
+/// すべて失敗した場合はスロット本体のエラーを返す
+///
+pub fn read_with_recovery<T, F>(
+    path: &Path,
+    generations: usize,
+    decode: F,
+) -> Result<Recovered<T>, SaveError>
+where
+    F: Fn(&[u8]) -> Result<T, SaveError>,
+{
+    let mut first_error = None;
+
+    for generation in 0..=generations {
+        let result = fs::read(generation_path(path, generation))
+            .map_err(SaveError::from)
+            .and_then(|buf| decode(&buf));
+
+        match result {
+            Ok(data) => return Ok(Recovered { data, generation }),
+            Err(e) => {
+                if first_error.is_none() {
+                    first_error = Some(e);
+                }
+            }
+        }
+    }
+
+    Err(first_error.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_text(buf: &[u8]) -> Result<String, SaveError> {
+        let text = String::from_utf8(buf.to_vec()).map_err(|e| SaveError::Parse(e.to_string()))?;
+        if text.starts_with("broken") {
+            return Err(SaveError::Parse(text));
+        }
+        Ok(text)
+    }
+
+    fn content(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn backups_rotate_up_to_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+
+        for n in 0..5 {
+            write_atomic(&path, format!("save {}", n).as_bytes(), BACKUP_GENERATIONS).unwrap();
+        }
+
+        assert_eq!(content(&path), "save 4");
+        for generation in 1..=BACKUP_GENERATIONS {
+            assert_eq!(
+                content(&generation_path(&path, generation)),
+                format!("save {}", 4 - generation)
+            );
+        }
+        assert!(!generation_path(&path, BACKUP_GENERATIONS + 1).exists());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn first_write_has_no_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+
+        write_atomic(&path, b"save", BACKUP_GENERATIONS).unwrap();
+
+        assert_eq!(content(&path), "save");
+        assert!(!generation_path(&path, 1).exists());
+    }
+
+    #[test]
+    fn temp_file_is_removed_when_write_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // ディレクトリはコピーできないので、バックアップを作るところで失敗する
+        let path = dir.path().join("Slot1");
+        fs::create_dir(&path).unwrap();
+
+        assert!(matches!(
+            write_atomic(&path, b"save", BACKUP_GENERATIONS),
+            Err(SaveError::Io(_))
+        ));
+        assert!(!temp_path(&path).exists());
+        assert!(path.is_dir());
+    }
+
+    #[test]
+    fn newest_readable_backup_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+        fs::write(&path, "broken slot").unwrap();
+        fs::write(generation_path(&path, 1), "broken bak1").unwrap();
+        fs::write(generation_path(&path, 2), "save 2").unwrap();
+        fs::write(generation_path(&path, 3), "save 3").unwrap();
+
+        let recovered = read_with_recovery(&path, BACKUP_GENERATIONS, read_text).unwrap();
+
+        assert_eq!(recovered.data, "save 2");
+        assert_eq!(recovered.generation, 2);
+        assert!(recovered.is_recovered_from_backup());
+    }
+
+    #[test]
+    fn missing_slot_is_recovered_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+        fs::write(generation_path(&path, 1), "save 1").unwrap();
+
+        let recovered = read_with_recovery(&path, BACKUP_GENERATIONS, read_text).unwrap();
+
+        assert_eq!(recovered.data, "save 1");
+        assert_eq!(recovered.generation, 1);
+    }
+
+    #[test]
+    fn slot_error_is_returned_when_every_generation_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+        fs::write(&path, "broken slot").unwrap();
+        fs::write(generation_path(&path, 2), "broken bak2").unwrap();
+
+        match read_with_recovery(&path, BACKUP_GENERATIONS, read_text) {
+            Err(SaveError::Parse(e)) => assert_eq!(e, "broken slot"),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("broken save was read"),
+        }
+
+        let missing = dir.path().join("Slot2");
+        assert!(matches!(
+            read_with_recovery(&missing, BACKUP_GENERATIONS, read_text),
+            Err(SaveError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn slot_itself_is_generation_zero() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Slot1");
+        write_atomic(&path, b"save", BACKUP_GENERATIONS).unwrap();
+
+        let recovered = read_with_recovery(&path, BACKUP_GENERATIONS, read_text).unwrap();
+
+        assert_eq!(recovered.generation, 0);
+        assert!(!recovered.is_recovered_from_backup());
+        assert_eq!(generation_path(&path, 0), path);
+    }
+}
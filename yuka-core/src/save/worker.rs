@@ -0,0 +1,276 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use super::{
+    codec::{self, Decoded, EncodeOptions, LegacyDecrypt},
+    error::SaveError,
+    storage::{self, Recovered},
+    NativeSaveData,
+};
+use crate::crypt::SaveKeys;
+
+pub enum SaveJob {
+    /// 呼び出し側で取ったスナップショットを暗号化して書き込む
+    Save {
+        slot: String,
+        path: PathBuf,
+        data: Box<NativeSaveData>,
+        options: EncodeOptions,
+    },
+    Load {
+        slot: String,
+        path: PathBuf,
+    },
+}
+
+pub enum SaveJobResult {
+    Saved {
+        slot: String,
+        /// 書き込んだスナップショットのrevision
+        revision: u64,
+        result: Result<(), SaveError>,
+    },
+    Loaded {
+        slot: String,
+        result: Result<Box<Recovered<Decoded>>, SaveError>,
+    },
+}
+
+///
+/// セーブとロードを別スレッドで行う
+/// ジョブは投入された順に一つずつ処理するので、同じスロットへの書き込みが競合することはない
+///
+pub struct SaveWorker {
+    jobs: Sender<SaveJob>,
+    results: Receiver<SaveJobResult>,
+    pending: usize,
+}
+
+impl SaveWorker {
+    pub fn spawn(keys: SaveKeys, legacy: LegacyDecrypt, generations: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<SaveJob>();
+        let (result_sender, results) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("save-worker".to_string())
+            .spawn(move || {
+                for job in job_receiver {
+                    let result = Self::run(job, &keys, legacy, generations);
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn save worker thread");
+
+        SaveWorker {
+            jobs,
+            results,
+            pending: 0,
+        }
+    }
+
+    fn run(
+        job: SaveJob,
+        keys: &SaveKeys,
+        legacy: LegacyDecrypt,
+        generations: usize,
+    ) -> SaveJobResult {
+        match job {
+            SaveJob::Save {
+                slot,
+                path,
+                mut data,
+                options,
+            } => {
+                let revision = data.get_revision();
+                let result = codec::encode_with(keys, &mut data, options).and_then(|sealed| {
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    storage::write_atomic(&path, sealed.as_slice(), generations)
+                });
+                SaveJobResult::Saved {
+                    slot,
+                    revision,
+                    result,
+                }
+            }
+            SaveJob::Load { slot, path } => {
+                let result = storage::read_with_recovery(&path, generations, |buf| {
+                    codec::decode(keys, buf, legacy)
+                })
+                .map(Box::new);
+                SaveJobResult::Loaded { slot, result }
+            }
+        }
+    }
+
+    pub fn submit(&mut self, job: SaveJob) {
+        // ワーカースレッドはSaveWorkerが生きている間は終了しないので、送信は失敗しない
+        self.jobs.send(job).expect("save worker thread has stopped");
+        self.pending += 1;
+    }
+
+    ///
+    /// 終わったジョブの結果をブロックせずに取り出す
+    ///
+    pub fn poll(&mut self) -> Vec<SaveJobResult> {
+        let finished = self.results.try_iter().collect::<Vec<_>>();
+        self.pending -= finished.len();
+        finished
+    }
+
+    ///
+    /// 投入済みのジョブがすべて終わるまで待つ
+    ///
+    pub fn wait_all(&mut self) -> Vec<SaveJobResult> {
+        let mut finished = Vec::new();
+
+        while self.pending > 0 {
+            match self.results.recv() {
+                Ok(result) => {
+                    finished.push(result);
+                    self.pending -= 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        finished
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemId;
+
+    fn no_legacy(_: &[u8]) -> Result<String, String> {
+        Err("not a legacy save".to_string())
+    }
+
+    fn spawn() -> SaveWorker {
+        SaveWorker::spawn(
+            SaveKeys::derive(b"yuka-core/save/worker/tests"),
+            no_legacy,
+            storage::BACKUP_GENERATIONS,
+        )
+    }
+
+    fn save_job(slot: &str, path: PathBuf, count: usize) -> SaveJob {
+        let mut data = NativeSaveData::new();
+        data.add_items(ItemId::new("soil.fuyodo"), count);
+
+        SaveJob::Save {
+            slot: slot.to_string(),
+            path,
+            data: Box::new(data),
+            options: EncodeOptions::default(),
+        }
+    }
+
+    fn describe(result: &SaveJobResult) -> String {
+        match result {
+            SaveJobResult::Saved { slot, result, .. } => {
+                format!("saved {} {}", slot, result.is_ok())
+            }
+            SaveJobResult::Loaded { slot, result } => match result {
+                Ok(loaded) => format!(
+                    "loaded {} {}",
+                    slot,
+                    loaded
+                        .data
+                        .data
+                        .get_items()
+                        .count(&ItemId::new("soil.fuyodo"))
+                ),
+                Err(_) => format!("load failed {}", slot),
+            },
+        }
+    }
+
+    #[test]
+    fn wait_all_returns_results_in_submitted_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut worker = spawn();
+
+        worker.submit(save_job("Slot1", dir.path().join("Slot1"), 1));
+        worker.submit(save_job("Slot1", dir.path().join("Slot1"), 2));
+        worker.submit(SaveJob::Load {
+            slot: "Slot1".to_string(),
+            path: dir.path().join("Slot1"),
+        });
+        worker.submit(SaveJob::Load {
+            slot: "Slot2".to_string(),
+            path: dir.path().join("Slot2"),
+        });
+        assert!(worker.is_busy());
+
+        let results = worker.wait_all().iter().map(describe).collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                "saved Slot1 true",
+                "saved Slot1 true",
+                "loaded Slot1 2",
+                "load failed Slot2",
+            ]
+        );
+        assert!(!worker.is_busy());
+        assert!(worker.poll().is_empty());
+        assert!(worker.wait_all().is_empty());
+    }
+
+    #[test]
+    fn poll_collects_finished_jobs_without_blocking() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut worker = spawn();
+        assert!(worker.poll().is_empty());
+
+        worker.submit(save_job("Slot1", dir.path().join("saves/Slot1"), 3));
+        worker.submit(SaveJob::Load {
+            slot: "Slot1".to_string(),
+            path: dir.path().join("saves/Slot1"),
+        });
+
+        let mut results = Vec::new();
+        while worker.is_busy() {
+            results.extend(worker.poll().iter().map(describe));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(results, ["saved Slot1 true", "loaded Slot1 3"]);
+        assert!(worker.wait_all().is_empty());
+    }
+
+    #[test]
+    fn saved_result_carries_snapshot_revision() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut worker = spawn();
+        let mut data = NativeSaveData::new();
+        data.add_items(ItemId::new("soil.fuyodo"), 1);
+        let revision = data.get_revision();
+
+        worker.submit(SaveJob::Save {
+            slot: "Slot1".to_string(),
+            path: dir.path().join("Slot1"),
+            data: Box::new(data),
+            options: EncodeOptions::default(),
+        });
+
+        match worker.wait_all().pop() {
+            Some(SaveJobResult::Saved {
+                revision: saved, ..
+            }) => assert_eq!(saved, revision),
+            _ => panic!("save result is missing"),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use toml::{value::Table, Value};
+
+use crate::save::{
+    autosave::AutosavePolicy,
+    format::{SaveFormat, DEFAULT_SAVE_FORMAT},
+};
+
+///
+/// 設定ファイルのバージョン
+/// 項目を足すだけなら上げなくてよい (足りない項目は既定値で読む)。意味や形を変えるときに上げる
+///
+pub const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsError {
+    Parse(String),
+    Serialize(String),
+    UnsupportedVersion(u32),
+    UnknownKey(String),
+    InvalidValue(String, String),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "failed to parse settings: {}", e),
+            Self::Serialize(e) => write!(f, "failed to serialize settings: {}", e),
+            Self::UnsupportedVersion(v) => write!(
+                f,
+                "settings version {} is newer than supported version {}",
+                v, SETTINGS_VERSION
+            ),
+            Self::UnknownKey(key) => write!(f, "unknown setting: {}", key),
+            Self::InvalidValue(key, e) => write!(f, "invalid value for {}: {}", key, e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+impl std::fmt::Display for WindowMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Windowed => write!(f, "windowed"),
+            Self::Fullscreen => write!(f, "fullscreen"),
+            Self::Borderless => write!(f, "borderless"),
+        }
+    }
+}
+
+///
+/// 音量。0.0 (無音) から 1.0 まで
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volumes {
+    pub master: f64,
+    pub bgm: f64,
+    pub se: f64,
+    pub voice: f64,
+}
+
+impl Default for Volumes {
+    fn default() -> Self {
+        Volumes {
+            master: 1.0,
+            bgm: 0.8,
+            se: 0.8,
+            voice: 1.0,
+        }
+    }
+}
+
+///
+/// セーブデータとは別に保存する、プレイヤーごとの設定
+/// 足りない項目は既定値で読むので、古い設定ファイルもそのまま読める
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSettings {
+    pub version: u32,
+    /// 1文字表示するごとの待ち時間 (秒)
+    pub text_interval_secs: f64,
+    pub auto_advance: bool,
+    /// 自動送りで、1行を表示し終えてから次に進むまでの待ち時間 (秒)
+    pub auto_advance_delay_secs: f64,
+    pub language: String,
+    pub window_mode: WindowMode,
+    pub autosave_policy: AutosavePolicy,
+    /// 新しく書き込むセーブの形式。読み込みはどの形式でもできる
+    pub save_format: SaveFormat,
+    // TOMLではテーブルを値より後に置く必要があるので最後にする
+    pub volume: Volumes,
+    /// アクション名 -> キーの名前 (OS.find_scancode_from_stringで読める名前)
+    /// 書かれていないアクションはプロジェクトの設定のまま
+    pub key_bindings: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            version: SETTINGS_VERSION,
+            text_interval_secs: 0.1,
+            auto_advance: false,
+            auto_advance_delay_secs: 1.5,
+            language: "ja".to_string(),
+            window_mode: WindowMode::default(),
+            autosave_policy: AutosavePolicy::default(),
+            save_format: DEFAULT_SAVE_FORMAT,
+            volume: Volumes::default(),
+            key_bindings: BTreeMap::new(),
+        }
+    }
+}
+
+fn clamp_or(value: f64, min: f64, max: f64, default: f64) -> f64 {
+    if value.is_finite() {
+        value.clamp(min, max)
+    } else {
+        default
+    }
+}
+
+impl GameSettings {
+    pub fn from_toml_str(s: &str) -> Result<Self, SettingsError> {
+        let value = toml::from_str::<Value>(s).map_err(|e| SettingsError::Parse(e.to_string()))?;
+
+        let version = match value.get("version") {
+            None => SETTINGS_VERSION,
+            Some(Value::Integer(v)) if *v >= 1 => *v as u32,
+            Some(v) => return Err(SettingsError::Parse(format!("invalid version: {}", v))),
+        };
+        if version > SETTINGS_VERSION {
+            return Err(SettingsError::UnsupportedVersion(version));
+        }
+
+        let mut settings: GameSettings = value
+            .try_into()
+            .map_err(|e| SettingsError::Parse(e.to_string()))?;
+        settings.version = SETTINGS_VERSION;
+        settings.normalize();
+
+        Ok(settings)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, SettingsError> {
+        toml::to_string(self).map_err(|e| SettingsError::Serialize(e.to_string()))
+    }
+
+    ///
+    /// 手で書き換えられた設定ファイルでも動くよう、範囲外の値を丸める
+    ///
+    pub fn normalize(&mut self) {
+        let default = GameSettings::default();
+
+        self.text_interval_secs = clamp_or(
+            self.text_interval_secs,
+            0.0,
+            1.0,
+            default.text_interval_secs,
+        );
+        self.auto_advance_delay_secs = clamp_or(
+            self.auto_advance_delay_secs,
+            0.0,
+            10.0,
+            default.auto_advance_delay_secs,
+        );
+
+        for (volume, default) in [
+            (&mut self.volume.master, default.volume.master),
+            (&mut self.volume.bgm, default.volume.bgm),
+            (&mut self.volume.se, default.volume.se),
+            (&mut self.volume.voice, default.volume.voice),
+        ] {
+            *volume = clamp_or(*volume, 0.0, 1.0, default);
+        }
+    }
+
+    fn to_table(&self) -> Table {
+        match Value::try_from(self) {
+            Ok(Value::Table(table)) => table,
+            _ => unreachable!("GameSettings always serializes to a table"),
+        }
+    }
+
+    ///
+    /// "volume.bgm" のように、ドットで区切った名前で設定を読む
+    ///
+    pub fn get(&self, key: &str) -> Option<Value> {
+        let mut value = Value::Table(self.to_table());
+
+        for segment in key.split('.') {
+            value = value.as_table_mut()?.remove(segment)?;
+        }
+
+        Some(value)
+    }
+
+    ///
+    /// ドットで区切った名前で設定を書き換え、値が変わったかを返す
+    /// key_bindings以外は、存在しない項目を作ることはできない
+    ///
+    pub fn set(&mut self, key: &str, value: Value) -> Result<bool, SettingsError> {
+        if key == "version" {
+            return Err(SettingsError::UnknownKey(key.to_string()));
+        }
+
+        let mut root = self.to_table();
+        let (parents, name) = match key.rsplit_once('.') {
+            Some((parents, name)) => (parents.split('.').collect::<Vec<_>>(), name),
+            None => (Vec::new(), key),
+        };
+
+        let mut table = &mut root;
+        for segment in &parents {
+            table = table
+                .get_mut(*segment)
+                .and_then(Value::as_table_mut)
+                .ok_or_else(|| SettingsError::UnknownKey(key.to_string()))?;
+        }
+
+        let is_binding = parents == ["key_bindings"];
+        if !is_binding && !table.contains_key(name) {
+            return Err(SettingsError::UnknownKey(key.to_string()));
+        }
+        table.insert(name.to_string(), value);
+
+        let mut settings: GameSettings = Value::Table(root)
+            .try_into()
+            .map_err(|e| SettingsError::InvalidValue(key.to_string(), e.to_string()))?;
+        settings.normalize();
+
+        let changed = settings != *self;
+        *self = settings;
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_version_is_read_as_current() {
+        let settings = GameSettings::from_toml_str("auto_advance = true").unwrap();
+
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert!(settings.auto_advance);
+        assert_eq!(settings.volume, Volumes::default());
+    }
+
+    #[test]
+    fn invalid_or_newer_version_is_rejected() {
+        assert_eq!(
+            GameSettings::from_toml_str(&format!("version = {}", SETTINGS_VERSION + 1)),
+            Err(SettingsError::UnsupportedVersion(SETTINGS_VERSION + 1))
+        );
+        for version in ["0", "-1", "\"1\""] {
+            assert!(matches!(
+                GameSettings::from_toml_str(&format!("version = {}", version)),
+                Err(SettingsError::Parse(_))
+            ));
+        }
+        assert!(matches!(
+            GameSettings::from_toml_str("version = "),
+            Err(SettingsError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = GameSettings {
+            window_mode: WindowMode::Borderless,
+            ..GameSettings::default()
+        };
+        settings
+            .key_bindings
+            .insert("ui_accept".to_string(), vec!["Enter".to_string()]);
+
+        let s = settings.to_toml_string().unwrap();
+
+        assert_eq!(GameSettings::from_toml_str(&s).unwrap(), settings);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let settings = GameSettings::from_toml_str(
+            r#"
+text_interval_secs = 5.0
+auto_advance_delay_secs = -1.0
+
+[volume]
+master = 2.0
+bgm = -0.5
+se = 0.3
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.text_interval_secs, 1.0);
+        assert_eq!(settings.auto_advance_delay_secs, 0.0);
+        assert_eq!(settings.volume.master, 1.0);
+        assert_eq!(settings.volume.bgm, 0.0);
+        assert_eq!(settings.volume.se, 0.3);
+        assert_eq!(settings.volume.voice, Volumes::default().voice);
+    }
+
+    #[test]
+    fn non_finite_values_are_reset_to_default() {
+        let mut settings = GameSettings {
+            text_interval_secs: f64::NAN,
+            volume: Volumes {
+                bgm: f64::INFINITY,
+                ..Volumes::default()
+            },
+            ..GameSettings::default()
+        };
+
+        settings.normalize();
+
+        assert_eq!(settings, GameSettings::default());
+    }
+
+    #[test]
+    fn get_reads_dotted_keys() {
+        let settings = GameSettings::default();
+
+        assert_eq!(settings.get("auto_advance"), Some(Value::Boolean(false)));
+        assert_eq!(settings.get("volume.bgm"), Some(Value::Float(0.8)));
+        assert_eq!(
+            settings.get("window_mode"),
+            Some(Value::String("windowed".to_string()))
+        );
+        assert!(settings.get("volume").unwrap().is_table());
+        assert_eq!(settings.get("volume.unknown"), None);
+        assert_eq!(settings.get("auto_advance.value"), None);
+        assert_eq!(settings.get("unknown"), None);
+    }
+
+    #[test]
+    fn set_changes_dotted_keys() {
+        let mut settings = GameSettings::default();
+
+        assert_eq!(settings.set("volume.bgm", Value::Float(0.5)), Ok(true));
+        assert_eq!(settings.volume.bgm, 0.5);
+        assert_eq!(settings.set("volume.bgm", Value::Float(0.5)), Ok(false));
+        assert_eq!(
+            settings.set("window_mode", Value::String("fullscreen".to_string())),
+            Ok(true)
+        );
+        assert_eq!(settings.window_mode, WindowMode::Fullscreen);
+
+        // 範囲外の値は丸めてから比べる
+        assert_eq!(settings.set("volume.master", Value::Float(3.0)), Ok(false));
+        assert_eq!(settings.volume.master, 1.0);
+    }
+
+    #[test]
+    fn set_rejects_unknown_keys() {
+        let mut settings = GameSettings::default();
+
+        for key in [
+            "unknown",
+            "volume.unknown",
+            "unknown.bgm",
+            "auto_advance.value",
+            "version",
+        ] {
+            assert_eq!(
+                settings.set(key, Value::Integer(1)),
+                Err(SettingsError::UnknownKey(key.to_string()))
+            );
+        }
+        assert_eq!(settings, GameSettings::default());
+    }
+
+    #[test]
+    fn set_inserts_key_bindings() {
+        let mut settings = GameSettings::default();
+        let keys = Value::Array(vec![Value::String("Space".to_string())]);
+
+        assert_eq!(
+            settings.set("key_bindings.ui_accept", keys.clone()),
+            Ok(true)
+        );
+        assert_eq!(
+            settings.key_bindings.get("ui_accept"),
+            Some(&vec!["Space".to_string()])
+        );
+        assert_eq!(settings.get("key_bindings.ui_accept"), Some(keys));
+        // key_bindingsの下のテーブルは作れない
+        assert_eq!(
+            settings.set("key_bindings.ui_accept.primary", Value::Integer(1)),
+            Err(SettingsError::UnknownKey(
+                "key_bindings.ui_accept.primary".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn set_rejects_invalid_values_without_changing_settings() {
+        let mut settings = GameSettings::default();
+
+        for (key, value) in [
+            ("auto_advance", Value::String("yes".to_string())),
+            ("window_mode", Value::String("tiny".to_string())),
+            ("volume.bgm", Value::String("loud".to_string())),
+            ("key_bindings.ui_accept", Value::Integer(1)),
+        ] {
+            assert!(matches!(
+                settings.set(key, value),
+                Err(SettingsError::InvalidValue(k, _)) if k == key
+            ));
+        }
+        assert_eq!(settings, GameSettings::default());
+    }
+}